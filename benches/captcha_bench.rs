@@ -1,5 +1,11 @@
-use captcha_rs::CaptchaBuilder;
-use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use captcha_rs::{CaptchaBuilder, Distortion, Sampling};
+use criterion::{BatchSize, BenchmarkId, Criterion, criterion_group, criterion_main};
+use rand::{Rng, rng};
+
+#[path = "../src/captcha/wavy_reference.rs"]
+mod wavy_reference;
+
+use wavy_reference::reference_wavy;
 
 fn bench_captcha_generation(c: &mut Criterion) {
     let mut group = c.benchmark_group("Captcha Generation");
@@ -54,5 +60,52 @@ fn bench_captcha_generation(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, bench_captcha_generation);
+/// Every case distorts a copy of the same pre-rendered captcha, so only the
+/// distortion pass is timed.
+fn bench_wavy_distortion(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Wavy Distortion");
+    let image = CaptchaBuilder::new()
+        .length(8)
+        .width(300)
+        .height(100)
+        .generate()
+        .image
+        .to_rgb8();
+
+    for level in [5, 20] {
+        group.bench_with_input(BenchmarkId::new("reference", level), &level, |b, &level| {
+            let mut rng = rng();
+            b.iter_batched(
+                || image.clone(),
+                |image| {
+                    let phase = rng.random_range(0.0..std::f32::consts::PI * 2.0);
+                    let amplitude = level as f32 * 1.5;
+                    let frequency = 0.05 + rng.random_range(0.0..0.05) * level as f32;
+                    reference_wavy(&image, amplitude, frequency, phase)
+                },
+                BatchSize::SmallInput,
+            );
+        });
+
+        for (name, sampling) in [
+            ("nearest", Sampling::Nearest),
+            ("bilinear", Sampling::Bilinear),
+        ] {
+            group.bench_with_input(BenchmarkId::new(name, level), &level, |b, &level| {
+                b.iter_batched(
+                    || image.clone(),
+                    |mut image| {
+                        Distortion::Wavy(level).apply(&mut image, sampling);
+                        image
+                    },
+                    BatchSize::SmallInput,
+                );
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_captcha_generation, bench_wavy_distortion);
 criterion_main!(benches);
@@ -0,0 +1,226 @@
+#ifndef CAPTCHA_RS_H
+#define CAPTCHA_RS_H
+
+/* Generated by cbindgen from src/capi.rs, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef struct CaptchaBuilder CaptchaBuilder;
+
+#define CAPTCHA_FORMAT_JPEG 0
+
+#define CAPTCHA_FORMAT_PNG 1
+
+#define CAPTCHA_SAMPLING_NEAREST 0
+
+#define CAPTCHA_SAMPLING_BILINEAR 1
+
+#define CAPTCHA_DISTORTION_WAVY 0
+
+#define CAPTCHA_DISTORTION_SWIRL 1
+
+#define CAPTCHA_DISTORTION_RIPPLE 2
+
+#define CAPTCHA_DISTORTION_FISHEYE 3
+
+#define CAPTCHA_DISTORTION_PERSPECTIVE 4
+
+#define CAPTCHA_DISTORTION_ELASTIC 5
+
+// A generated captcha with its text and encoded image ready to be borrowed.
+typedef struct Captcha Captcha;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// A builder with the default settings of `CaptchaBuilder::new`.
+CaptchaBuilder *captcha_builder_new(void);
+
+// A copy of `builder` with the same settings.
+//
+// # Safety
+// `builder` must be null or a live builder handle.
+CaptchaBuilder *captcha_builder_clone(const CaptchaBuilder *builder);
+
+// # Safety
+// `builder` must be null or a builder handle not used afterwards.
+void captcha_builder_free(CaptchaBuilder *builder);
+
+// Fixed text instead of a random one. Returns `false` if `text` isn't UTF-8.
+//
+// # Safety
+// `builder` must be null or a live builder handle, `text` null or a C string.
+bool captcha_builder_text(CaptchaBuilder *builder, const char *text);
+
+// Characters to draw random text from. Returns `false` if `chars` isn't UTF-8.
+//
+// # Safety
+// `builder` must be null or a live builder handle, `chars` null or a C string.
+bool captcha_builder_chars(CaptchaBuilder *builder, const char *chars);
+
+// # Safety
+// `builder` must be null or a live builder handle.
+void captcha_builder_length(CaptchaBuilder *builder, size_t length);
+
+// # Safety
+// `builder` must be null or a live builder handle.
+void captcha_builder_width(CaptchaBuilder *builder, uint32_t width);
+
+// # Safety
+// `builder` must be null or a live builder handle.
+void captcha_builder_height(CaptchaBuilder *builder, uint32_t height);
+
+// # Safety
+// `builder` must be null or a live builder handle.
+void captcha_builder_dark_mode(CaptchaBuilder *builder, bool dark_mode);
+
+// # Safety
+// `builder` must be null or a live builder handle.
+void captcha_builder_complexity(CaptchaBuilder *builder, uint32_t complexity);
+
+// # Safety
+// `builder` must be null or a live builder handle.
+void captcha_builder_compression(CaptchaBuilder *builder, uint8_t compression);
+
+// # Safety
+// `builder` must be null or a live builder handle.
+void captcha_builder_drop_shadow(CaptchaBuilder *builder, bool drop_shadow);
+
+// # Safety
+// `builder` must be null or a live builder handle.
+void captcha_builder_interference_lines(CaptchaBuilder *builder, size_t lines);
+
+// # Safety
+// `builder` must be null or a live builder handle.
+void captcha_builder_interference_ellipses(CaptchaBuilder *builder, size_t ellipses);
+
+// # Safety
+// `builder` must be null or a live builder handle.
+void captcha_builder_interference_shapes(CaptchaBuilder *builder, size_t shapes);
+
+// # Safety
+// `builder` must be null or a live builder handle.
+void captcha_builder_stroke_width(CaptchaBuilder *builder, uint32_t width);
+
+// # Safety
+// `builder` must be null or a live builder handle.
+void captcha_builder_antialias(CaptchaBuilder *builder, bool antialias);
+
+// # Safety
+// `builder` must be null or a live builder handle.
+void captcha_builder_filled_ellipses(CaptchaBuilder *builder, bool filled);
+
+// # Safety
+// `builder` must be null or a live builder handle.
+void captcha_builder_distortion(CaptchaBuilder *builder, uint32_t distortion);
+
+// Add a `CAPTCHA_DISTORTION_*` distortion. Returns `false` for an unknown kind.
+//
+// # Safety
+// `builder` must be null or a live builder handle.
+bool captcha_builder_add_distortion(CaptchaBuilder *builder, uint32_t kind, uint32_t level);
+
+// Set a `CAPTCHA_SAMPLING_*` mode. Returns `false` for an unknown mode.
+//
+// # Safety
+// `builder` must be null or a live builder handle.
+bool captcha_builder_sampling(CaptchaBuilder *builder, uint32_t sampling);
+
+// # Safety
+// `builder` must be null or a live builder handle.
+void captcha_builder_strike_through(CaptchaBuilder *builder, bool strike_through);
+
+// # Safety
+// `builder` must be null or a live builder handle.
+void captcha_builder_glyph_arcs(CaptchaBuilder *builder, size_t arcs);
+
+// Add a TrueType or OpenType font from its file contents, which are copied.
+// Returns `false` if the font can't be parsed.
+//
+// # Safety
+// `builder` must be null or a live builder handle, `data` null or valid for `len`
+// bytes.
+bool captcha_builder_font(CaptchaBuilder *builder, const uint8_t *data, size_t len);
+
+// Set a `CAPTCHA_FORMAT_*` output format. Returns `false` for an unknown format.
+//
+// # Safety
+// `builder` must be null or a live builder handle.
+bool captcha_builder_format(CaptchaBuilder *builder, uint32_t format);
+
+// Reproducible output for tests, never for captchas served to users.
+//
+// # Safety
+// `builder` must be null or a live builder handle.
+void captcha_builder_seed(CaptchaBuilder *builder, uint64_t seed);
+
+// Generate a captcha. The builder stays usable.
+//
+// # Safety
+// `builder` must be null or a live builder handle.
+struct Captcha *captcha_build(const CaptchaBuilder *builder);
+
+// # Safety
+// `captcha` must be null or a captcha handle not used afterwards.
+void captcha_free(struct Captcha *captcha);
+
+// The answer, as a UTF-8 string owned by the captcha.
+//
+// # Safety
+// `captcha` must be null or a live captcha handle.
+const char *captcha_text(const struct Captcha *captcha);
+
+// The encoded image owned by the captcha, its size written to `len`.
+//
+// # Safety
+// `captcha` must be null or a live captcha handle, `len` null or writable.
+const uint8_t *captcha_bytes(const struct Captcha *captcha, size_t *len);
+
+// MIME type of the encoded image, a static string.
+//
+// # Safety
+// `captcha` must be null or a live captcha handle.
+const char *captcha_mime_type(const struct Captcha *captcha);
+
+// The image as a data URI, to be released with `captcha_string_free`.
+//
+// # Safety
+// `captcha` must be null or a live captcha handle.
+char *captcha_base64(const struct Captcha *captcha);
+
+// Compare an answer using the captcha's match policy.
+//
+// # Safety
+// `captcha` must be null or a live captcha handle, `answer` null or a C string.
+bool captcha_matches(const struct Captcha *captcha, const char *answer);
+
+// A stateless token signed with `secret`, valid for `expiration_seconds`, to be
+// released with `captcha_string_free`. Null if it can't be signed.
+//
+// # Safety
+// `captcha` must be null or a live captcha handle, `secret` null or a C string.
+char *captcha_token(const struct Captcha *captcha, const char *secret, uint64_t expiration_seconds);
+
+// Check an answer against a token from `captcha_token`: 1 if it is correct, 0 if
+// it is wrong and -1 if the token is invalid or expired.
+//
+// # Safety
+// `token`, `answer` and `secret` must each be null or a C string.
+int32_t captcha_verify(const char *token, const char *answer, const char *secret);
+
+// Release a string returned by `captcha_base64` or `captcha_token`.
+//
+// # Safety
+// `value` must be null or a string returned by this library, not used afterwards.
+void captcha_string_free(char *value);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CAPTCHA_RS_H */
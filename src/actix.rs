@@ -0,0 +1,307 @@
+//! [actix-web](https://docs.rs/actix-web) integration: captcha handlers and a
+//! [`VerifiedCaptcha`] extractor, both using a shared [`CaptchaService`].
+
+use std::future::Future;
+use std::pin::Pin;
+
+use actix_web::dev::Payload;
+use actix_web::http::StatusCode;
+use actix_web::http::header::{self, CacheControl, CacheDirective};
+use actix_web::web::{self, Data, ServiceConfig};
+use actix_web::{FromRequest, HttpRequest, HttpResponse, ResponseError};
+use serde::de::DeserializeOwned;
+
+use crate::web::{
+    CaptchaRejection, CaptchaService, ErrorBody, Submission, VerifyBody, VerifyResult,
+};
+
+/// Register `service` as application data and route `GET /captcha` (JSON with the
+/// token and a data URI), `GET /captcha/image` (the raw image, token in the
+/// `X-Captcha-Token` header) and `POST /captcha/verify` (JSON or form body with
+/// `token` and `answer`).
+///
+/// ```rust
+/// use actix_web::App;
+/// use captcha_rs::{CaptchaBuilder, CaptchaService, MemoryStore};
+/// use std::sync::Arc;
+///
+/// let service = CaptchaService::with_store(CaptchaBuilder::new(), Arc::new(MemoryStore::new()));
+/// let app = App::new().configure(captcha_rs::actix::configure(service));
+/// ```
+pub fn configure(service: CaptchaService) -> impl FnOnce(&mut ServiceConfig) {
+    move |config| {
+        config
+            .app_data(Data::new(service))
+            .route("/captcha", web::get().to(challenge))
+            .route("/captcha/image", web::get().to(image))
+            .route("/captcha/verify", web::post().to(verify));
+    }
+}
+
+fn no_store() -> CacheControl {
+    CacheControl(vec![CacheDirective::NoStore])
+}
+
+fn failed_to_issue() -> HttpResponse {
+    HttpResponse::InternalServerError()
+        .insert_header(no_store())
+        .json(ErrorBody {
+            error: String::from("failed to issue captcha"),
+        })
+}
+
+/// Handler returning a new captcha as JSON: `{"token", "image", "expires_in"}`.
+pub async fn challenge(service: Data<CaptchaService>) -> HttpResponse {
+    let body = web::block(move || {
+        let challenge = service.issue()?;
+        Some(challenge.body(service.expires_in()))
+    });
+    match body.await.ok().flatten() {
+        Some(body) => HttpResponse::Ok().insert_header(no_store()).json(body),
+        None => failed_to_issue(),
+    }
+}
+
+/// Handler returning a new captcha as a raw image, with its token in the
+/// `X-Captcha-Token` header.
+pub async fn image(service: Data<CaptchaService>) -> HttpResponse {
+    let image = web::block(move || {
+        let challenge = service.issue()?;
+        let mime_type = challenge.captcha.format.mime_type();
+        Some((challenge.token, mime_type, challenge.captcha.to_bytes()))
+    });
+    match image.await.ok().flatten() {
+        Some((token, mime_type, bytes)) => HttpResponse::Ok()
+            .content_type(mime_type)
+            .insert_header(no_store())
+            .insert_header(("X-Captcha-Token", token))
+            .body(bytes),
+        None => failed_to_issue(),
+    }
+}
+
+/// Handler checking a JSON or form body with `token` and `answer`, returning
+/// `{"valid": bool}`.
+pub async fn verify(
+    service: Data<CaptchaService>,
+    request: HttpRequest,
+    payload: web::Payload,
+) -> Result<HttpResponse, CaptchaRejection> {
+    let body: VerifyBody = parse_body(&request, &mut payload.into_inner()).await?;
+    let client = client(&request);
+    let valid = web::block(move || service.verify(&body.token, &body.answer, client.as_deref()));
+    Ok(HttpResponse::Ok()
+        .insert_header(no_store())
+        .json(VerifyResult {
+            valid: valid.await.unwrap_or(false),
+        }))
+}
+
+/// The peer address, used as the rate limiting key.
+fn client(request: &HttpRequest) -> Option<String> {
+    request.peer_addr().map(|addr| addr.ip().to_string())
+}
+
+/// Deserialize a JSON body, or a form body for any other content type.
+async fn parse_body<T>(request: &HttpRequest, payload: &mut Payload) -> Result<T, CaptchaRejection>
+where
+    T: DeserializeOwned + 'static,
+{
+    let json = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+
+    if json {
+        web::Json::<T>::from_request(request, payload)
+            .await
+            .map(web::Json::into_inner)
+            .map_err(|_| CaptchaRejection::InvalidBody)
+    } else {
+        web::Form::<T>::from_request(request, payload)
+            .await
+            .map(web::Form::into_inner)
+            .map_err(|_| CaptchaRejection::InvalidBody)
+    }
+}
+
+impl ResponseError for CaptchaRejection {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code())
+            .insert_header(no_store())
+            .json(self.body())
+    }
+}
+
+/// Extracts `T` from a JSON or form body that also carries `captcha_token` and
+/// `captcha_answer` fields, and rejects the request unless the captcha verifies.
+///
+/// The [`CaptchaService`] is read from the application data registered by
+/// [`configure`] (or `App::app_data(Data::new(service))`). Attempts are counted
+/// against the peer address.
+///
+/// ```rust
+/// use captcha_rs::actix::VerifiedCaptcha;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Signup {
+///     email: String,
+/// }
+///
+/// async fn signup(VerifiedCaptcha(form): VerifiedCaptcha<Signup>) -> String {
+///     format!("welcome {}", form.email)
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct VerifiedCaptcha<T>(pub T);
+
+impl<T> FromRequest for VerifiedCaptcha<T>
+where
+    T: DeserializeOwned + 'static,
+{
+    type Error = CaptchaRejection;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(request: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let request = request.clone();
+        let mut payload = payload.take();
+        Box::pin(async move {
+            let service = request
+                .app_data::<Data<CaptchaService>>()
+                .cloned()
+                .ok_or(CaptchaRejection::NotConfigured)?;
+            let submission: Submission<T> = parse_body(&request, &mut payload).await?;
+            let (token, answer, inner) = submission.into_parts()?;
+            let client = client(&request);
+            let valid = web::block(move || service.verify(&token, &answer, client.as_deref()));
+            if !valid.await.unwrap_or(false) {
+                return Err(CaptchaRejection::Failed);
+            }
+            Ok(VerifiedCaptcha(inner))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{VerifiedCaptcha, configure};
+    use crate::{CaptchaBuilder, CaptchaService, ChallengeBody, MemoryStore};
+    use actix_web::http::{StatusCode, header};
+    use actix_web::{App, test, web};
+    use serde::Deserialize;
+    use std::sync::Arc;
+
+    #[derive(Deserialize)]
+    struct Signup {
+        email: String,
+    }
+
+    async fn signup(VerifiedCaptcha(form): VerifiedCaptcha<Signup>) -> String {
+        form.email
+    }
+
+    fn service() -> CaptchaService {
+        let builder = CaptchaBuilder::new().text(String::from("actix"));
+        CaptchaService::with_store(builder, Arc::new(MemoryStore::new()))
+    }
+
+    #[actix_web::test]
+    async fn it_serves_and_verifies_captchas() {
+        let app = test::init_service(App::new().configure(configure(service()))).await;
+
+        let request = test::TestRequest::get().uri("/captcha").to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(
+            response.headers().get(header::CACHE_CONTROL).unwrap(),
+            "no-store"
+        );
+        let body: ChallengeBody = test::read_body_json(response).await;
+        assert!(body.image.starts_with("data:image/jpeg;base64,"));
+
+        let form = [("token", body.token.as_str()), ("answer", "ACTIX")];
+        let request = test::TestRequest::post()
+            .uri("/captcha/verify")
+            .set_form(form)
+            .to_request();
+        let result: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(result["valid"], true);
+
+        let request = test::TestRequest::post()
+            .uri("/captcha/verify")
+            .set_json(serde_json::json!({ "token": body.token, "answer": "actix" }))
+            .to_request();
+        let result: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(result["valid"], false);
+
+        let request = test::TestRequest::get().uri("/captcha/image").to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(
+            response.headers().get(header::CONTENT_TYPE).unwrap(),
+            "image/jpeg"
+        );
+        assert!(response.headers().contains_key("x-captcha-token"));
+    }
+
+    #[actix_web::test]
+    async fn it_guards_handlers_with_the_extractor() {
+        let app = test::init_service(
+            App::new()
+                .configure(configure(service()))
+                .route("/signup", web::post().to(signup)),
+        )
+        .await;
+
+        let issue = || test::TestRequest::get().uri("/captcha").to_request();
+        let body: ChallengeBody = test::call_and_read_body_json(&app, issue()).await;
+        let request = test::TestRequest::post()
+            .uri("/signup")
+            .set_form([
+                ("email", "a@example.com"),
+                ("captcha_token", &body.token),
+                ("captcha_answer", "actix"),
+            ])
+            .to_request();
+        assert_eq!(
+            test::call_and_read_body(&app, request).await,
+            "a@example.com"
+        );
+
+        let body: ChallengeBody = test::call_and_read_body_json(&app, issue()).await;
+        let request = test::TestRequest::post()
+            .uri("/signup")
+            .set_json(serde_json::json!({
+                "email": "b@example.com",
+                "captcha_token": body.token,
+                "captcha_answer": "nope",
+            }))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let request = test::TestRequest::post()
+            .uri("/signup")
+            .set_form([("email", "c@example.com")])
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body["error"], "missing captcha");
+    }
+
+    #[actix_web::test]
+    async fn it_reports_a_missing_service() {
+        let app = test::init_service(App::new().route("/signup", web::post().to(signup))).await;
+        let request = test::TestRequest::post()
+            .uri("/signup")
+            .set_form([("email", "d@example.com")])
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -0,0 +1,338 @@
+//! [axum](https://docs.rs/axum) integration: a captcha router and a
+//! [`VerifiedCaptcha`] extractor, both using a shared [`CaptchaService`].
+
+use std::net::SocketAddr;
+
+use axum::Router;
+use axum::extract::{ConnectInfo, Form, FromRef, FromRequest, Json, Request, State};
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use serde::de::DeserializeOwned;
+
+use crate::web::{
+    CaptchaRejection, CaptchaService, ErrorBody, Submission, VerifyBody, VerifyResult,
+};
+
+/// Routes `GET /captcha` (JSON with the token and a data URI), `GET /captcha/image`
+/// (the raw image, token in the `X-Captcha-Token` header) and
+/// `POST /captcha/verify` (JSON or form body with `token` and `answer`).
+///
+/// Attempts are rate limited by the peer address when the app is served with
+/// `into_make_service_with_connect_info::<SocketAddr>()`.
+///
+/// ```rust
+/// use axum::Router;
+/// use captcha_rs::{CaptchaBuilder, CaptchaService, MemoryStore};
+/// use std::sync::Arc;
+///
+/// let service = CaptchaService::with_store(CaptchaBuilder::new(), Arc::new(MemoryStore::new()));
+/// let app: Router = Router::new().merge(captcha_rs::axum::router(service));
+/// ```
+pub fn router<S>(service: CaptchaService) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/captcha", get(challenge))
+        .route("/captcha/image", get(image))
+        .route("/captcha/verify", post(verify))
+        .with_state(service)
+}
+
+fn failed_to_issue() -> Response {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(ErrorBody {
+            error: String::from("failed to issue captcha"),
+        }),
+    )
+        .into_response()
+}
+
+/// Run `work` on tokio's blocking pool, so rendering images and hashing answers
+/// doesn't stall the async workers. `None` if it panicked.
+async fn blocking<T, F>(work: F) -> Option<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(work).await.ok()
+}
+
+async fn challenge(State(service): State<CaptchaService>) -> Response {
+    let body = blocking(move || {
+        let challenge = service.issue()?;
+        Some(challenge.body(service.expires_in()))
+    });
+    match body.await.flatten() {
+        Some(body) => ([(header::CACHE_CONTROL, "no-store")], Json(body)).into_response(),
+        None => failed_to_issue(),
+    }
+}
+
+async fn image(State(service): State<CaptchaService>) -> Response {
+    let image = blocking(move || {
+        let challenge = service.issue()?;
+        let mime_type = challenge.captcha.format.mime_type();
+        Some((challenge.token, mime_type, challenge.captcha.to_bytes()))
+    });
+    match image.await.flatten() {
+        Some((token, mime_type, bytes)) => (
+            [
+                (header::CONTENT_TYPE, mime_type),
+                (header::CACHE_CONTROL, "no-store"),
+                (header::HeaderName::from_static("x-captcha-token"), &token),
+            ],
+            bytes,
+        )
+            .into_response(),
+        None => failed_to_issue(),
+    }
+}
+
+async fn verify(State(service): State<CaptchaService>, request: Request) -> Response {
+    let client = client(&request);
+    match parse_body::<VerifyBody, _>(request, &()).await {
+        Ok(body) => {
+            let valid =
+                blocking(move || service.verify(&body.token, &body.answer, client.as_deref()));
+            Json(VerifyResult {
+                valid: valid.await.unwrap_or(false),
+            })
+            .into_response()
+        }
+        Err(rejection) => rejection.into_response(),
+    }
+}
+
+/// The peer address from [`ConnectInfo`], used as the rate limiting key.
+fn client(request: &Request) -> Option<String> {
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_string())
+}
+
+/// Deserialize a JSON body, or a form body for any other content type.
+async fn parse_body<T, S>(request: Request, state: &S) -> Result<T, CaptchaRejection>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    let json = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+
+    if json {
+        Json::<T>::from_request(request, state)
+            .await
+            .map(|Json(body)| body)
+            .map_err(|_| CaptchaRejection::InvalidBody)
+    } else {
+        Form::<T>::from_request(request, state)
+            .await
+            .map(|Form(body)| body)
+            .map_err(|_| CaptchaRejection::InvalidBody)
+    }
+}
+
+impl IntoResponse for CaptchaRejection {
+    fn into_response(self) -> Response {
+        let status =
+            StatusCode::from_u16(self.status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        (
+            status,
+            [(header::CACHE_CONTROL, "no-store")],
+            Json(self.body()),
+        )
+            .into_response()
+    }
+}
+
+/// Extracts `T` from a JSON or form body that also carries `captcha_token` and
+/// `captcha_answer` fields, and rejects the request unless the captcha verifies.
+///
+/// The state must provide a [`CaptchaService`] through `FromRef`. Attempts are
+/// counted against the peer address if [`ConnectInfo`] is available.
+///
+/// ```rust
+/// use captcha_rs::axum::VerifiedCaptcha;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Signup {
+///     email: String,
+/// }
+///
+/// async fn signup(VerifiedCaptcha(form): VerifiedCaptcha<Signup>) -> String {
+///     format!("welcome {}", form.email)
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct VerifiedCaptcha<T>(pub T);
+
+impl<T, S> FromRequest<S> for VerifiedCaptcha<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+    CaptchaService: FromRef<S>,
+{
+    type Rejection = CaptchaRejection;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let service = CaptchaService::from_ref(state);
+        let client = client(&request);
+        let submission: Submission<T> = parse_body(request, state).await?;
+        let (token, answer, inner) = submission.into_parts()?;
+        let valid = blocking(move || service.verify(&token, &answer, client.as_deref()));
+        if !valid.await.unwrap_or(false) {
+            return Err(CaptchaRejection::Failed);
+        }
+        Ok(VerifiedCaptcha(inner))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{VerifiedCaptcha, router};
+    use crate::{CaptchaBuilder, CaptchaService, ChallengeBody, MemoryStore};
+    use axum::Router;
+    use axum::body::{Body, to_bytes};
+    use axum::http::{Request, StatusCode, header};
+    use axum::routing::post;
+    use serde::Deserialize;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    #[derive(Deserialize)]
+    struct Signup {
+        email: String,
+    }
+
+    async fn signup(VerifiedCaptcha(form): VerifiedCaptcha<Signup>) -> String {
+        form.email
+    }
+
+    fn app() -> Router {
+        let builder = CaptchaBuilder::new().text(String::from("axum"));
+        let service = CaptchaService::with_store(builder, Arc::new(MemoryStore::new()));
+        Router::new()
+            .route("/signup", post(signup))
+            .with_state(service.clone())
+            .merge(router(service))
+    }
+
+    async fn send(app: &Router, request: Request<Body>) -> (StatusCode, String) {
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8_lossy(&body).into_owned())
+    }
+
+    fn post_body(uri: &str, content_type: &str, body: String) -> Request<Body> {
+        Request::post(uri)
+            .header(header::CONTENT_TYPE, content_type)
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    async fn token(app: &Router) -> String {
+        let (status, body) = send(app, Request::get("/captcha").body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        let body: ChallengeBody = serde_json::from_str(&body).unwrap();
+        assert!(body.image.starts_with("data:image/jpeg;base64,"));
+        body.token
+    }
+
+    #[tokio::test]
+    async fn it_serves_and_verifies_captchas() {
+        let app = app();
+
+        let json = format!(r#"{{"token": "{}", "answer": "AXUM"}}"#, token(&app).await);
+        let (status, body) = send(
+            &app,
+            post_body("/captcha/verify", "application/json", json.clone()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, r#"{"valid":true}"#);
+
+        let (_, body) = send(&app, post_body("/captcha/verify", "application/json", json)).await;
+        assert_eq!(body, r#"{"valid":false}"#);
+
+        let response = app
+            .clone()
+            .oneshot(Request::get("/captcha/image").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/jpeg");
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-store");
+        assert!(response.headers().contains_key("x-captcha-token"));
+    }
+
+    #[tokio::test]
+    async fn it_guards_handlers_with_the_extractor() {
+        let app = app();
+        let form = "application/x-www-form-urlencoded";
+
+        let body = format!(
+            "email=a%40example.com&captcha_token={}&captcha_answer=axum",
+            token(&app).await
+        );
+        let (status, body) = send(&app, post_body("/signup", form, body)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "a@example.com");
+
+        let body = format!(
+            r#"{{"email": "b@example.com", "captcha_token": "{}", "captcha_answer": "nope"}}"#,
+            token(&app).await
+        );
+        let (status, _) = send(&app, post_body("/signup", "application/json", body)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, body) = send(&app, post_body("/signup", form, String::from("email=c"))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body, r#"{"error":"missing captcha"}"#);
+
+        let (status, _) = send(
+            &app,
+            post_body("/signup", "application/json", String::from("{")),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[cfg(feature = "stateless")]
+    #[tokio::test]
+    async fn it_rate_limits_by_peer_address() {
+        use crate::{Key, KeySet, MemoryRateLimiter, Verifier};
+        use axum::extract::ConnectInfo;
+        use std::net::SocketAddr;
+
+        let verifier = Verifier::new(KeySet::new(Key::unnamed("axum-secret")))
+            .rate_limiter(Arc::new(MemoryRateLimiter::new().backoff(60, 60)));
+        let builder = CaptchaBuilder::new().text(String::from("axum"));
+        let app = router(CaptchaService::with_verifier(builder, verifier));
+
+        let verify = |token: String, answer: &str, peer: &str| {
+            let json = format!(r#"{{"token": "{}", "answer": "{}"}}"#, token, answer);
+            let mut request = post_body("/captcha/verify", "application/json", json);
+            let addr: SocketAddr = peer.parse().unwrap();
+            request.extensions_mut().insert(ConnectInfo(addr));
+            request
+        };
+
+        let request = verify(token(&app).await, "nope", "203.0.113.7:4000");
+        assert_eq!(send(&app, request).await.1, r#"{"valid":false}"#);
+
+        // The failed peer is locked out, another one is not
+        let request = verify(token(&app).await, "axum", "203.0.113.7:4001");
+        assert_eq!(send(&app, request).await.1, r#"{"valid":false}"#);
+        let request = verify(token(&app).await, "axum", "198.51.100.2:4000");
+        assert_eq!(send(&app, request).await.1, r#"{"valid":true}"#);
+    }
+}
@@ -0,0 +1,328 @@
+//! Command-line captcha generator.
+//!
+//! Every `CaptchaBuilder` option is available as a flag, on top of an optional
+//! JSON `CaptchaConfig` file. Images are written to files or printed as data URIs.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use captcha_rs::{Captcha, CaptchaBuilder, CaptchaConfig, Distortion, OutputFormat, Sampling};
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser};
+use serde_json::json;
+
+#[derive(Parser)]
+#[command(name = "captcha-rs", version, about = "Generate captcha images")]
+struct Args {
+    /// JSON `CaptchaConfig` file, overridden by the flags below
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Fixed answer instead of random characters
+    #[arg(long)]
+    text: Option<String>,
+
+    /// Number of characters
+    #[arg(short, long)]
+    length: Option<usize>,
+
+    /// Characters to draw the answer from
+    #[arg(long)]
+    chars: Option<String>,
+
+    /// Image size
+    #[arg(long, value_name = "WIDTHxHEIGHT", value_parser = parse_size)]
+    size: Option<(u32, u32)>,
+
+    /// Dark background
+    #[arg(long)]
+    dark: bool,
+
+    /// Noise level, from 1 to 10
+    #[arg(short, long)]
+    complexity: Option<u32>,
+
+    /// JPEG quality, from 1 to 99
+    #[arg(long)]
+    compression: Option<u8>,
+
+    /// Wavy distortion level, from 0 to 100
+    #[arg(long)]
+    distortion: Option<u32>,
+
+    /// Extra distortion, e.g. `swirl:40` (wavy, swirl, ripple, fisheye, perspective,
+    /// elastic). Repeatable
+    #[arg(long = "warp", value_name = "KIND:LEVEL", value_parser = parse_distortion)]
+    warps: Vec<Distortion>,
+
+    /// Distortion sampling: nearest or bilinear
+    #[arg(long, value_parser = parse_sampling)]
+    sampling: Option<Sampling>,
+
+    /// Drop shadow under the characters
+    #[arg(long)]
+    drop_shadow: bool,
+
+    /// Number of interference lines
+    #[arg(long)]
+    interference_lines: Option<usize>,
+
+    /// Number of distraction ellipses
+    #[arg(long)]
+    interference_ellipses: Option<usize>,
+
+    /// Number of random polygons, arcs and dot clusters
+    #[arg(long)]
+    interference_shapes: Option<usize>,
+
+    /// Width of interference strokes, from 1 to 10
+    #[arg(long)]
+    stroke_width: Option<u32>,
+
+    /// Anti-alias interference strokes
+    #[arg(long)]
+    antialias: bool,
+
+    /// Fill distraction ellipses
+    #[arg(long)]
+    filled_ellipses: bool,
+
+    /// Curve crossing every character
+    #[arg(long)]
+    strike_through: bool,
+
+    /// Number of arcs joining adjacent characters
+    #[arg(long)]
+    glyph_arcs: Option<usize>,
+
+    /// TrueType or OpenType font file. Repeatable
+    #[arg(long = "font", value_name = "FILE")]
+    fonts: Vec<PathBuf>,
+
+    /// Image format: jpeg or png
+    #[arg(long, value_parser = parse_format)]
+    format: Option<OutputFormat>,
+
+    /// Seed for reproducible output. The n-th captcha uses SEED + n
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Number of captchas to generate
+    #[arg(short = 'n', long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    count: u64,
+
+    /// Write the image to this file (single captcha only)
+    #[arg(short, long, value_name = "FILE", conflicts_with = "out_dir")]
+    output: Option<PathBuf>,
+
+    /// Write the images into this directory, created if missing
+    #[arg(long, value_name = "DIR")]
+    out_dir: Option<PathBuf>,
+
+    /// Print one JSON object per captcha instead of tab-separated text
+    #[arg(long)]
+    json: bool,
+
+    /// Secret signing a stateless token for every captcha
+    #[cfg(feature = "stateless")]
+    #[arg(long, env = "CAPTCHA_SECRET", hide_env_values = true)]
+    secret: Option<String>,
+
+    /// Token lifetime in seconds
+    #[cfg(feature = "stateless")]
+    #[arg(long, default_value_t = 300)]
+    expires: u64,
+}
+
+impl Args {
+    /// The configuration file, if any, with the flags applied on top.
+    fn config(&self) -> io::Result<CaptchaConfig> {
+        let mut config = match &self.config {
+            Some(path) => serde_json::from_slice(&fs::read(path)?)?,
+            None => CaptchaConfig::default(),
+        };
+
+        if let Some(length) = self.length {
+            config.length = length;
+        }
+        if let Some(chars) = &self.chars {
+            config.chars = chars.clone();
+        }
+        if let Some((width, height)) = self.size {
+            config.width = width;
+            config.height = height;
+        }
+        config.dark_mode |= self.dark;
+        if let Some(complexity) = self.complexity {
+            config.complexity = complexity;
+        }
+        if let Some(compression) = self.compression {
+            config.compression = compression;
+        }
+        if let Some(distortion) = self.distortion {
+            config.distortion = distortion;
+        }
+        config.distortions.extend(&self.warps);
+        if let Some(sampling) = self.sampling {
+            config.sampling = sampling;
+        }
+        config.drop_shadow |= self.drop_shadow;
+        if let Some(lines) = self.interference_lines {
+            config.interference_lines = lines;
+        }
+        if let Some(ellipses) = self.interference_ellipses {
+            config.interference_ellipses = ellipses;
+        }
+        if let Some(shapes) = self.interference_shapes {
+            config.interference_shapes = shapes;
+        }
+        if let Some(width) = self.stroke_width {
+            config.stroke_width = width;
+        }
+        config.antialias |= self.antialias;
+        config.filled_ellipses |= self.filled_ellipses;
+        config.strike_through |= self.strike_through;
+        if let Some(arcs) = self.glyph_arcs {
+            config.glyph_arcs = arcs;
+        }
+        config.fonts.extend(self.fonts.iter().cloned());
+        if let Some(format) = self.format {
+            config.format = format;
+        }
+
+        Ok(config)
+    }
+
+    #[cfg(feature = "stateless")]
+    fn token(&self, captcha: &Captcha) -> Option<String> {
+        let secret = self.secret.as_deref()?;
+        captcha.as_token(secret, self.expires)
+    }
+
+    #[cfg(not(feature = "stateless"))]
+    fn token(&self, _captcha: &Captcha) -> Option<String> {
+        None
+    }
+}
+
+fn parse_size(value: &str) -> Result<(u32, u32), String> {
+    let (width, height) = value
+        .split_once(['x', 'X'])
+        .ok_or_else(|| format!("expected WIDTHxHEIGHT, got `{}`", value))?;
+    let width = width
+        .parse()
+        .map_err(|_| format!("invalid width `{}`", width))?;
+    let height = height
+        .parse()
+        .map_err(|_| format!("invalid height `{}`", height))?;
+    Ok((width, height))
+}
+
+fn parse_distortion(value: &str) -> Result<Distortion, String> {
+    let (kind, level) = value
+        .split_once(':')
+        .ok_or_else(|| format!("expected KIND:LEVEL, got `{}`", value))?;
+    let level = level
+        .parse()
+        .map_err(|_| format!("invalid level `{}`", level))?;
+    match kind {
+        "wavy" => Ok(Distortion::Wavy(level)),
+        "swirl" => Ok(Distortion::Swirl(level)),
+        "ripple" => Ok(Distortion::Ripple(level)),
+        "fisheye" => Ok(Distortion::Fisheye(level)),
+        "perspective" => Ok(Distortion::Perspective(level)),
+        "elastic" => Ok(Distortion::Elastic(level)),
+        _ => Err(format!("unknown distortion `{}`", kind)),
+    }
+}
+
+fn parse_sampling(value: &str) -> Result<Sampling, String> {
+    match value {
+        "nearest" => Ok(Sampling::Nearest),
+        "bilinear" => Ok(Sampling::Bilinear),
+        _ => Err(format!("unknown sampling `{}`", value)),
+    }
+}
+
+fn parse_format(value: &str) -> Result<OutputFormat, String> {
+    match value {
+        "jpeg" | "jpg" => Ok(OutputFormat::Jpeg),
+        "png" => Ok(OutputFormat::Png),
+        _ => Err(format!("unknown format `{}`", value)),
+    }
+}
+
+fn run(args: &Args) -> io::Result<()> {
+    let mut builder: CaptchaBuilder = args.config()?.builder()?;
+    if let Some(text) = &args.text {
+        builder = builder.text(text.clone());
+    }
+    if let Some(dir) = &args.out_dir {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut stdout = io::stdout().lock();
+    for n in 0..args.count {
+        if let Some(seed) = args.seed {
+            builder = builder.seed(seed.wrapping_add(n));
+        }
+        let captcha = builder.generate();
+
+        let file = match (&args.output, &args.out_dir) {
+            (Some(path), _) => Some(path.clone()),
+            (None, Some(dir)) => {
+                Some(dir.join(format!("captcha-{}.{}", n + 1, captcha.format.extension())))
+            }
+            (None, None) => None,
+        };
+        if let Some(file) = &file {
+            fs::write(file, captcha.to_bytes())?;
+        }
+        let token = args.token(&captcha);
+
+        if args.json {
+            let mut line = json!({ "text": captcha.text });
+            match &file {
+                Some(file) => line["file"] = json!(file),
+                None => line["image"] = json!(captcha.to_base64()),
+            }
+            if let Some(token) = token {
+                line["token"] = json!(token);
+            }
+            writeln!(stdout, "{}", line)?;
+        } else {
+            let image = match &file {
+                Some(file) => file.display().to_string(),
+                None => captcha.to_base64(),
+            };
+            match token {
+                Some(token) => writeln!(stdout, "{}\t{}\t{}", captcha.text, image, token)?,
+                None => writeln!(stdout, "{}\t{}", captcha.text, image)?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    if args.output.is_some() && args.count > 1 {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--output writes a single captcha, use --out-dir with --count",
+            )
+            .exit();
+    }
+
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,225 @@
+//! Standalone HTTP captcha service.
+//!
+//! `GET /captcha` returns the image as a data URI with a token (a store id, or a
+//! signed token in stateless mode), `GET /captcha/image` returns the raw image
+//! with the token in a header and `POST /verify` checks an answer. Settings come from an optional
+//! JSON file given as the only argument.
+
+use std::fs;
+use std::io::{self, Read};
+use std::sync::Arc;
+use std::thread;
+
+use captcha_rs::{CaptchaConfig, CaptchaRejection, CaptchaService, MemoryStore, VerifyBody};
+#[cfg(feature = "stateless")]
+use captcha_rs::{Key, KeySet, MemoryReplayGuard, Verifier};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// Largest accepted request body.
+const MAX_BODY: u64 = 16 * 1024;
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ServerConfig {
+    /// Address to listen on. Port 0 picks a free port.
+    bind: String,
+    /// Threads handling requests.
+    workers: usize,
+    /// Seconds a captcha stays valid.
+    expires: u64,
+    /// Sign stateless tokens with this secret instead of keeping answers in memory.
+    /// `CAPTCHA_SECRET` overrides it.
+    secret: Option<String>,
+    /// Maximum number of captchas kept in memory.
+    store_capacity: usize,
+    captcha: CaptchaConfig,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind: String::from("127.0.0.1:8080"),
+            workers: 4,
+            expires: 300,
+            secret: None,
+            store_capacity: 10_000,
+            captcha: CaptchaConfig::default(),
+        }
+    }
+}
+
+struct Service {
+    captcha: CaptchaService,
+}
+
+impl Service {
+    fn new(config: &ServerConfig) -> io::Result<Self> {
+        let secret = std::env::var("CAPTCHA_SECRET")
+            .ok()
+            .or_else(|| config.secret.clone());
+        let builder = config.captcha.builder()?;
+        let captcha = match secret {
+            // Signed tokens, each accepted once
+            #[cfg(feature = "stateless")]
+            Some(secret) => CaptchaService::with_verifier(
+                builder,
+                Verifier::new(KeySet::new(Key::hmac("server", secret)))
+                    .replay_guard(Arc::new(MemoryReplayGuard::new())),
+            ),
+            #[cfg(not(feature = "stateless"))]
+            Some(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "a secret requires the stateless feature",
+                ));
+            }
+            None => CaptchaService::with_store(
+                builder,
+                Arc::new(MemoryStore::new().capacity(config.store_capacity)),
+            ),
+        };
+
+        Ok(Service {
+            captcha: captcha.expires(config.expires),
+        })
+    }
+
+    fn handle(&self, mut request: Request) -> io::Result<()> {
+        let path = request
+            .url()
+            .split('?')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        let response = match (request.method(), path.as_str()) {
+            (Method::Get, "/captcha") => self.captcha_json(),
+            (Method::Get, "/captcha/image") => self.captcha_image(),
+            (Method::Post, "/verify") => {
+                let mut body = Vec::new();
+                request
+                    .as_reader()
+                    .take(MAX_BODY + 1)
+                    .read_to_end(&mut body)?;
+                if body.len() as u64 > MAX_BODY {
+                    json_response(413, &json!({ "error": "request body too large" }))
+                } else {
+                    let client = request.remote_addr().map(|addr| addr.ip().to_string());
+                    self.verify(&body, client.as_deref())
+                }
+            }
+            (_, "/captcha" | "/captcha/image" | "/verify") => {
+                json_response(405, &json!({ "error": "method not allowed" }))
+            }
+            _ => json_response(404, &json!({ "error": "not found" })),
+        };
+        request.respond(response)
+    }
+
+    fn verify(&self, body: &[u8], client: Option<&str>) -> Response<io::Cursor<Vec<u8>>> {
+        match serde_json::from_slice::<VerifyBody>(body) {
+            Ok(body) => json_response(
+                200,
+                &json!({ "valid": self.captcha.verify(&body.token, &body.answer, client) }),
+            ),
+            Err(_) => rejection_response(CaptchaRejection::InvalidBody),
+        }
+    }
+
+    fn captcha_json(&self) -> Response<io::Cursor<Vec<u8>>> {
+        match self.captcha.issue() {
+            Some(challenge) => {
+                json_response(200, &json!(challenge.body(self.captcha.expires_in())))
+            }
+            None => json_response(500, &json!({ "error": "failed to issue captcha" })),
+        }
+    }
+
+    fn captcha_image(&self) -> Response<io::Cursor<Vec<u8>>> {
+        let Some(challenge) = self.captcha.issue() else {
+            return json_response(500, &json!({ "error": "failed to issue captcha" }));
+        };
+        let captcha = &challenge.captcha;
+        let headers = [
+            header("Content-Type", captcha.format.mime_type()),
+            header("Cache-Control", "no-store"),
+            header("X-Captcha-Token", &challenge.token),
+        ];
+        match headers.into_iter().collect::<Option<Vec<_>>>() {
+            Some(headers) => headers.into_iter().fold(
+                Response::from_data(captcha.to_bytes()),
+                Response::with_header,
+            ),
+            None => json_response(500, &json!({ "error": "invalid response header" })),
+        }
+    }
+}
+
+/// A response header, or `None` if the value can't be sent in a header.
+fn header(name: &str, value: &str) -> Option<Header> {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).ok()
+}
+
+fn json_response(status: u16, body: &Value) -> Response<io::Cursor<Vec<u8>>> {
+    [
+        header("Content-Type", "application/json"),
+        header("Cache-Control", "no-store"),
+    ]
+    .into_iter()
+    .flatten()
+    .fold(
+        Response::from_data(body.to_string().into_bytes()).with_status_code(status),
+        Response::with_header,
+    )
+}
+
+fn rejection_response(rejection: CaptchaRejection) -> Response<io::Cursor<Vec<u8>>> {
+    json_response(
+        rejection.status(),
+        &json!({ "error": rejection.to_string() }),
+    )
+}
+
+fn run() -> io::Result<()> {
+    let config: ServerConfig = match std::env::args_os().nth(1) {
+        Some(path) => serde_json::from_slice(&fs::read(path)?)?,
+        None => ServerConfig::default(),
+    };
+    let service = Arc::new(Service::new(&config)?);
+    let server = Arc::new(Server::http(&config.bind).map_err(io::Error::other)?);
+
+    match server.server_addr().to_ip() {
+        Some(addr) => println!("listening on http://{}", addr),
+        None => println!("listening on {}", server.server_addr()),
+    }
+
+    let workers: Vec<_> = (0..config.workers.max(1))
+        .map(|_| {
+            let server = Arc::clone(&server);
+            let service = Arc::clone(&service);
+            thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    if let Err(err) = service.handle(request) {
+                        eprintln!("error: {}", err);
+                    }
+                }
+            })
+        })
+        .collect();
+
+    for worker in workers {
+        let _ = worker.join();
+    }
+    Ok(())
+}
+
+fn main() -> std::process::ExitCode {
+    match run() {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,548 @@
+//! C ABI for generating and verifying captchas from other languages.
+//!
+//! Builders and captchas are opaque handles released with `captcha_builder_free`
+//! and `captcha_free`. Strings returned as `char *` are owned by the caller and
+//! released with `captcha_string_free`; `const` pointers are borrowed from the
+//! captcha and valid until it is freed. Every function accepts null handles and
+//! strings, doing nothing or returning null, `false` or an error value.
+//!
+//! The header is generated with
+//! `cbindgen --config cbindgen.toml --output include/captcha_rs.h src/capi.rs`.
+
+use std::ffi::{CStr, CString, c_char};
+use std::ptr;
+
+use ab_glyph::FontArc;
+
+use crate::{Captcha, CaptchaBuilder, Distortion, OutputFormat, Sampling};
+
+pub const CAPTCHA_FORMAT_JPEG: u32 = 0;
+pub const CAPTCHA_FORMAT_PNG: u32 = 1;
+
+pub const CAPTCHA_SAMPLING_NEAREST: u32 = 0;
+pub const CAPTCHA_SAMPLING_BILINEAR: u32 = 1;
+
+pub const CAPTCHA_DISTORTION_WAVY: u32 = 0;
+pub const CAPTCHA_DISTORTION_SWIRL: u32 = 1;
+pub const CAPTCHA_DISTORTION_RIPPLE: u32 = 2;
+pub const CAPTCHA_DISTORTION_FISHEYE: u32 = 3;
+pub const CAPTCHA_DISTORTION_PERSPECTIVE: u32 = 4;
+pub const CAPTCHA_DISTORTION_ELASTIC: u32 = 5;
+
+/// A generated captcha with its text and encoded image ready to be borrowed.
+pub struct CaptchaHandle {
+    captcha: Captcha,
+    text: CString,
+    bytes: Vec<u8>,
+}
+
+/// Borrow a C string as UTF-8.
+unsafe fn str_arg<'a>(value: *const c_char) -> Option<&'a str> {
+    if value.is_null() {
+        return None;
+    }
+    unsafe { CStr::from_ptr(value) }.to_str().ok()
+}
+
+/// Hand a string over to C, to be released with `captcha_string_free`.
+fn string_result(value: String) -> *mut c_char {
+    match CString::new(value) {
+        Ok(value) => value.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Apply a consuming builder setter in place.
+unsafe fn update(
+    builder: *mut CaptchaBuilder,
+    f: impl FnOnce(CaptchaBuilder) -> CaptchaBuilder,
+) -> bool {
+    match unsafe { builder.as_mut() } {
+        Some(builder) => {
+            *builder = f(std::mem::take(builder));
+            true
+        }
+        None => false,
+    }
+}
+
+/// A builder with the default settings of `CaptchaBuilder::new`.
+#[unsafe(no_mangle)]
+pub extern "C" fn captcha_builder_new() -> *mut CaptchaBuilder {
+    Box::into_raw(Box::new(CaptchaBuilder::new()))
+}
+
+/// A copy of `builder` with the same settings.
+///
+/// # Safety
+/// `builder` must be null or a live builder handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_builder_clone(
+    builder: *const CaptchaBuilder,
+) -> *mut CaptchaBuilder {
+    match unsafe { builder.as_ref() } {
+        Some(builder) => Box::into_raw(Box::new(builder.clone())),
+        None => ptr::null_mut(),
+    }
+}
+
+/// # Safety
+/// `builder` must be null or a builder handle not used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_builder_free(builder: *mut CaptchaBuilder) {
+    if !builder.is_null() {
+        drop(unsafe { Box::from_raw(builder) });
+    }
+}
+
+/// Fixed text instead of a random one. Returns `false` if `text` isn't UTF-8.
+///
+/// # Safety
+/// `builder` must be null or a live builder handle, `text` null or a C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_builder_text(
+    builder: *mut CaptchaBuilder,
+    text: *const c_char,
+) -> bool {
+    match unsafe { str_arg(text) } {
+        Some(text) => unsafe { update(builder, |b| b.text(text.to_string())) },
+        None => false,
+    }
+}
+
+/// Characters to draw random text from. Returns `false` if `chars` isn't UTF-8.
+///
+/// # Safety
+/// `builder` must be null or a live builder handle, `chars` null or a C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_builder_chars(
+    builder: *mut CaptchaBuilder,
+    chars: *const c_char,
+) -> bool {
+    match unsafe { str_arg(chars) } {
+        Some(chars) => unsafe { update(builder, |b| b.chars(chars.chars().collect())) },
+        None => false,
+    }
+}
+
+/// # Safety
+/// `builder` must be null or a live builder handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_builder_length(builder: *mut CaptchaBuilder, length: usize) {
+    unsafe { update(builder, |b| b.length(length)) };
+}
+
+/// # Safety
+/// `builder` must be null or a live builder handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_builder_width(builder: *mut CaptchaBuilder, width: u32) {
+    unsafe { update(builder, |b| b.width(width)) };
+}
+
+/// # Safety
+/// `builder` must be null or a live builder handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_builder_height(builder: *mut CaptchaBuilder, height: u32) {
+    unsafe { update(builder, |b| b.height(height)) };
+}
+
+/// # Safety
+/// `builder` must be null or a live builder handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_builder_dark_mode(builder: *mut CaptchaBuilder, dark_mode: bool) {
+    unsafe { update(builder, |b| b.dark_mode(dark_mode)) };
+}
+
+/// # Safety
+/// `builder` must be null or a live builder handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_builder_complexity(builder: *mut CaptchaBuilder, complexity: u32) {
+    unsafe { update(builder, |b| b.complexity(complexity)) };
+}
+
+/// # Safety
+/// `builder` must be null or a live builder handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_builder_compression(
+    builder: *mut CaptchaBuilder,
+    compression: u8,
+) {
+    unsafe { update(builder, |b| b.compression(compression)) };
+}
+
+/// # Safety
+/// `builder` must be null or a live builder handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_builder_drop_shadow(
+    builder: *mut CaptchaBuilder,
+    drop_shadow: bool,
+) {
+    unsafe { update(builder, |b| b.drop_shadow(drop_shadow)) };
+}
+
+/// # Safety
+/// `builder` must be null or a live builder handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_builder_interference_lines(
+    builder: *mut CaptchaBuilder,
+    lines: usize,
+) {
+    unsafe { update(builder, |b| b.interference_lines(lines)) };
+}
+
+/// # Safety
+/// `builder` must be null or a live builder handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_builder_interference_ellipses(
+    builder: *mut CaptchaBuilder,
+    ellipses: usize,
+) {
+    unsafe { update(builder, |b| b.interference_ellipses(ellipses)) };
+}
+
+/// # Safety
+/// `builder` must be null or a live builder handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_builder_interference_shapes(
+    builder: *mut CaptchaBuilder,
+    shapes: usize,
+) {
+    unsafe { update(builder, |b| b.interference_shapes(shapes)) };
+}
+
+/// # Safety
+/// `builder` must be null or a live builder handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_builder_stroke_width(builder: *mut CaptchaBuilder, width: u32) {
+    unsafe { update(builder, |b| b.stroke_width(width)) };
+}
+
+/// # Safety
+/// `builder` must be null or a live builder handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_builder_antialias(builder: *mut CaptchaBuilder, antialias: bool) {
+    unsafe { update(builder, |b| b.antialias(antialias)) };
+}
+
+/// # Safety
+/// `builder` must be null or a live builder handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_builder_filled_ellipses(
+    builder: *mut CaptchaBuilder,
+    filled: bool,
+) {
+    unsafe { update(builder, |b| b.filled_ellipses(filled)) };
+}
+
+/// # Safety
+/// `builder` must be null or a live builder handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_builder_distortion(builder: *mut CaptchaBuilder, distortion: u32) {
+    unsafe { update(builder, |b| b.distortion(distortion)) };
+}
+
+/// Add a `CAPTCHA_DISTORTION_*` distortion. Returns `false` for an unknown kind.
+///
+/// # Safety
+/// `builder` must be null or a live builder handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_builder_add_distortion(
+    builder: *mut CaptchaBuilder,
+    kind: u32,
+    level: u32,
+) -> bool {
+    let distortion = match kind {
+        CAPTCHA_DISTORTION_WAVY => Distortion::Wavy(level),
+        CAPTCHA_DISTORTION_SWIRL => Distortion::Swirl(level),
+        CAPTCHA_DISTORTION_RIPPLE => Distortion::Ripple(level),
+        CAPTCHA_DISTORTION_FISHEYE => Distortion::Fisheye(level),
+        CAPTCHA_DISTORTION_PERSPECTIVE => Distortion::Perspective(level),
+        CAPTCHA_DISTORTION_ELASTIC => Distortion::Elastic(level),
+        _ => return false,
+    };
+    unsafe { update(builder, |b| b.add_distortion(distortion)) }
+}
+
+/// Set a `CAPTCHA_SAMPLING_*` mode. Returns `false` for an unknown mode.
+///
+/// # Safety
+/// `builder` must be null or a live builder handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_builder_sampling(
+    builder: *mut CaptchaBuilder,
+    sampling: u32,
+) -> bool {
+    let sampling = match sampling {
+        CAPTCHA_SAMPLING_NEAREST => Sampling::Nearest,
+        CAPTCHA_SAMPLING_BILINEAR => Sampling::Bilinear,
+        _ => return false,
+    };
+    unsafe { update(builder, |b| b.sampling(sampling)) }
+}
+
+/// # Safety
+/// `builder` must be null or a live builder handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_builder_strike_through(
+    builder: *mut CaptchaBuilder,
+    strike_through: bool,
+) {
+    unsafe { update(builder, |b| b.strike_through(strike_through)) };
+}
+
+/// # Safety
+/// `builder` must be null or a live builder handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_builder_glyph_arcs(builder: *mut CaptchaBuilder, arcs: usize) {
+    unsafe { update(builder, |b| b.glyph_arcs(arcs)) };
+}
+
+/// Add a TrueType or OpenType font from its file contents, which are copied.
+/// Returns `false` if the font can't be parsed.
+///
+/// # Safety
+/// `builder` must be null or a live builder handle, `data` null or valid for `len`
+/// bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_builder_font(
+    builder: *mut CaptchaBuilder,
+    data: *const u8,
+    len: usize,
+) -> bool {
+    if data.is_null() {
+        return false;
+    }
+    let data = unsafe { std::slice::from_raw_parts(data, len) };
+    match FontArc::try_from_vec(data.to_vec()) {
+        Ok(font) => unsafe { update(builder, |b| b.font(font)) },
+        Err(_) => false,
+    }
+}
+
+/// Set a `CAPTCHA_FORMAT_*` output format. Returns `false` for an unknown format.
+///
+/// # Safety
+/// `builder` must be null or a live builder handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_builder_format(builder: *mut CaptchaBuilder, format: u32) -> bool {
+    let format = match format {
+        CAPTCHA_FORMAT_JPEG => OutputFormat::Jpeg,
+        CAPTCHA_FORMAT_PNG => OutputFormat::Png,
+        _ => return false,
+    };
+    unsafe { update(builder, |b| b.format(format)) }
+}
+
+/// Reproducible output for tests, never for captchas served to users.
+///
+/// # Safety
+/// `builder` must be null or a live builder handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_builder_seed(builder: *mut CaptchaBuilder, seed: u64) {
+    unsafe { update(builder, |b| b.seed(seed)) };
+}
+
+/// Generate a captcha. The builder stays usable.
+///
+/// # Safety
+/// `builder` must be null or a live builder handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_build(builder: *const CaptchaBuilder) -> *mut CaptchaHandle {
+    let Some(builder) = (unsafe { builder.as_ref() }) else {
+        return ptr::null_mut();
+    };
+    let captcha = builder.generate();
+    let handle = CaptchaHandle {
+        text: CString::new(captcha.text.as_str()).unwrap_or_default(),
+        bytes: captcha.to_bytes(),
+        captcha,
+    };
+    Box::into_raw(Box::new(handle))
+}
+
+/// # Safety
+/// `captcha` must be null or a captcha handle not used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_free(captcha: *mut CaptchaHandle) {
+    if !captcha.is_null() {
+        drop(unsafe { Box::from_raw(captcha) });
+    }
+}
+
+/// The answer, as a UTF-8 string owned by the captcha.
+///
+/// # Safety
+/// `captcha` must be null or a live captcha handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_text(captcha: *const CaptchaHandle) -> *const c_char {
+    match unsafe { captcha.as_ref() } {
+        Some(captcha) => captcha.text.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// The encoded image owned by the captcha, its size written to `len`.
+///
+/// # Safety
+/// `captcha` must be null or a live captcha handle, `len` null or writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_bytes(
+    captcha: *const CaptchaHandle,
+    len: *mut usize,
+) -> *const u8 {
+    let (data, size) = match unsafe { captcha.as_ref() } {
+        Some(captcha) => (captcha.bytes.as_ptr(), captcha.bytes.len()),
+        None => (ptr::null(), 0),
+    };
+    if let Some(len) = unsafe { len.as_mut() } {
+        *len = size;
+    }
+    data
+}
+
+/// MIME type of the encoded image, a static string.
+///
+/// # Safety
+/// `captcha` must be null or a live captcha handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_mime_type(captcha: *const CaptchaHandle) -> *const c_char {
+    match unsafe { captcha.as_ref() }.map(|captcha| captcha.captcha.format) {
+        Some(OutputFormat::Jpeg) => c"image/jpeg".as_ptr(),
+        Some(OutputFormat::Png) => c"image/png".as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// The image as a data URI, to be released with `captcha_string_free`.
+///
+/// # Safety
+/// `captcha` must be null or a live captcha handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_base64(captcha: *const CaptchaHandle) -> *mut c_char {
+    match unsafe { captcha.as_ref() } {
+        Some(captcha) => string_result(captcha.captcha.to_base64()),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Compare an answer using the captcha's match policy.
+///
+/// # Safety
+/// `captcha` must be null or a live captcha handle, `answer` null or a C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_matches(
+    captcha: *const CaptchaHandle,
+    answer: *const c_char,
+) -> bool {
+    match (unsafe { captcha.as_ref() }, unsafe { str_arg(answer) }) {
+        (Some(captcha), Some(answer)) => captcha.captcha.matches(answer),
+        _ => false,
+    }
+}
+
+/// A stateless token signed with `secret`, valid for `expiration_seconds`, to be
+/// released with `captcha_string_free`. Null if it can't be signed.
+///
+/// # Safety
+/// `captcha` must be null or a live captcha handle, `secret` null or a C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_token(
+    captcha: *const CaptchaHandle,
+    secret: *const c_char,
+    expiration_seconds: u64,
+) -> *mut c_char {
+    let (Some(captcha), Some(secret)) = (unsafe { captcha.as_ref() }, unsafe { str_arg(secret) })
+    else {
+        return ptr::null_mut();
+    };
+    match captcha.captcha.as_token(secret, expiration_seconds) {
+        Some(token) => string_result(token),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Check an answer against a token from `captcha_token`: 1 if it is correct, 0 if
+/// it is wrong and -1 if the token is invalid or expired.
+///
+/// # Safety
+/// `token`, `answer` and `secret` must each be null or a C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_verify(
+    token: *const c_char,
+    answer: *const c_char,
+    secret: *const c_char,
+) -> i32 {
+    let (Some(token), Some(answer), Some(secret)) =
+        (unsafe { (str_arg(token), str_arg(answer), str_arg(secret)) })
+    else {
+        return -1;
+    };
+    match crate::verify(token, answer, secret) {
+        Some(true) => 1,
+        Some(false) => 0,
+        None => -1,
+    }
+}
+
+/// Release a string returned by `captcha_base64` or `captcha_token`.
+///
+/// # Safety
+/// `value` must be null or a string returned by this library, not used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn captcha_string_free(value: *mut c_char) {
+    if !value.is_null() {
+        drop(unsafe { CString::from_raw(value) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_builds_and_verifies_through_the_c_abi() {
+        unsafe {
+            let builder = captcha_builder_new();
+            assert!(captcha_builder_text(builder, c"Abc12".as_ptr()));
+            assert!(captcha_builder_format(builder, CAPTCHA_FORMAT_PNG));
+            assert!(!captcha_builder_format(builder, 9));
+            assert!(captcha_builder_add_distortion(
+                builder,
+                CAPTCHA_DISTORTION_SWIRL,
+                20
+            ));
+            captcha_builder_width(builder, 160);
+
+            let captcha = captcha_build(builder);
+            assert_eq!(CStr::from_ptr(captcha_text(captcha)), c"Abc12");
+            let mut len = 0;
+            let bytes = captcha_bytes(captcha, &mut len);
+            assert!(std::slice::from_raw_parts(bytes, len).starts_with(b"\x89PNG"));
+            assert_eq!(CStr::from_ptr(captcha_mime_type(captcha)), c"image/png");
+            assert!(captcha_matches(captcha, c"abc12".as_ptr()));
+
+            let token = captcha_token(captcha, c"capi-secret".as_ptr(), 60);
+            assert!(!token.is_null());
+            assert_eq!(
+                captcha_verify(token, c"abc12".as_ptr(), c"capi-secret".as_ptr()),
+                1
+            );
+            assert_eq!(
+                captcha_verify(token, c"nope".as_ptr(), c"capi-secret".as_ptr()),
+                0
+            );
+            assert_eq!(
+                captcha_verify(token, c"abc12".as_ptr(), c"other".as_ptr()),
+                -1
+            );
+            captcha_string_free(token);
+
+            captcha_free(captcha);
+            captcha_builder_free(builder);
+
+            assert!(captcha_build(ptr::null()).is_null());
+            assert!(!captcha_builder_text(ptr::null_mut(), c"x".as_ptr()));
+            assert_eq!(captcha_verify(ptr::null(), ptr::null(), ptr::null()), -1);
+        }
+    }
+}
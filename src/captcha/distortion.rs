@@ -0,0 +1,270 @@
+use image::{ImageBuffer, Luma, Rgb};
+use imageproc::filter::gaussian_blur_f32;
+use imageproc::geometric_transformations::Projection;
+use rand::Rng;
+
+use super::random::rng;
+use super::standard::{Sampling, apply_wavy_distortion, sample_bilinear};
+
+/// Maximum number of distortions that can be stacked on one captcha.
+pub const MAX_DISTORTIONS: usize = 8;
+
+/// A geometric warp applied to the rendered text.
+///
+/// Every variant takes a strength level from 0 (no effect) to 100. Parameters such
+/// as the warp center or the displacement field are randomized per captcha.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Distortion {
+    /// Sine wave along both axes, same as `CaptchaBuilder::distortion`.
+    Wavy(u32),
+    /// Rotation around a random center, strongest in the middle of the swirl.
+    Swirl(u32),
+    /// Concentric waves radiating from a random center.
+    Ripple(u32),
+    /// Barrel (fisheye) bulge magnifying the middle of the image.
+    Fisheye(u32),
+    /// Random perspective transform obtained by moving the image corners.
+    Perspective(u32),
+    /// Random displacement field smoothed by a Gaussian blur.
+    Elastic(u32),
+}
+
+impl Distortion {
+    /// Apply this distortion to the image.
+    pub fn apply(&self, image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, sampling: Sampling) {
+        if image.width() < 2 || image.height() < 2 {
+            return;
+        }
+        match *self {
+            Distortion::Wavy(level) => apply_wavy_distortion(image, level.min(100), sampling),
+            Distortion::Swirl(level) => apply_swirl(image, level.min(100), sampling),
+            Distortion::Ripple(level) => apply_ripple(image, level.min(100), sampling),
+            Distortion::Fisheye(level) => apply_fisheye(image, level.min(100), sampling),
+            Distortion::Perspective(level) => apply_perspective(image, level.min(100), sampling),
+            Distortion::Elastic(level) => apply_elastic(image, level.min(100), sampling),
+        }
+    }
+}
+
+/// Rebuild the image by sampling the source position returned by `map` for every
+/// output pixel. Positions outside the image are clamped to its edges.
+fn remap<F>(image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, sampling: Sampling, map: F)
+where
+    F: Fn(f32, f32) -> (f32, f32),
+{
+    let width = image.width();
+    let height = image.height();
+    let stride = width as usize * 3;
+    let src: &[u8] = image.as_raw();
+    let mut out = vec![0u8; src.len()];
+
+    for (y, dst_row) in out.chunks_exact_mut(stride).enumerate() {
+        for (x, dst) in dst_row.chunks_exact_mut(3).enumerate() {
+            let (src_x, src_y) = map(x as f32, y as f32);
+            match sampling {
+                Sampling::Nearest => {
+                    let sx = src_x.round().clamp(0.0, (width - 1) as f32) as usize;
+                    let sy = src_y.round().clamp(0.0, (height - 1) as f32) as usize;
+                    let idx = sy * stride + sx * 3;
+                    dst.copy_from_slice(&src[idx..idx + 3]);
+                }
+                Sampling::Bilinear => {
+                    dst.copy_from_slice(&sample_bilinear(src, width, height, src_x, src_y));
+                }
+            }
+        }
+    }
+
+    image.copy_from_slice(&out);
+}
+
+/// Pick a random point in the middle half of the image.
+fn random_center(width: u32, height: u32) -> (f32, f32) {
+    let mut rng = rng();
+    let cx = rng.random_range(0.25..=0.75) * width as f32;
+    let cy = rng.random_range(0.25..=0.75) * height as f32;
+    (cx, cy)
+}
+
+fn apply_swirl(image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, level: u32, sampling: Sampling) {
+    if level == 0 {
+        return;
+    }
+    let (cx, cy) = random_center(image.width(), image.height());
+    let radius = image.width().max(image.height()) as f32 * 0.5;
+    let direction = if rng().random_bool(0.5) { 1.0 } else { -1.0 };
+    // Up to ~2.5 radians of twist at the center for level 100
+    let strength = direction * level as f32 * 0.025;
+
+    remap(image, sampling, |x, y| {
+        let (dx, dy) = (x - cx, y - cy);
+        let distance = (dx * dx + dy * dy).sqrt();
+        if distance >= radius {
+            return (x, y);
+        }
+        let falloff = 1.0 - distance / radius;
+        let angle = strength * falloff * falloff;
+        let (sin, cos) = angle.sin_cos();
+        (cx + dx * cos - dy * sin, cy + dx * sin + dy * cos)
+    });
+}
+
+fn apply_ripple(image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, level: u32, sampling: Sampling) {
+    if level == 0 {
+        return;
+    }
+    let mut rng = rng();
+    let (cx, cy) = random_center(image.width(), image.height());
+    let amplitude = level as f32 * 0.08;
+    let wavelength: f32 = rng.random_range(8.0..20.0);
+    let phase: f32 = rng.random_range(0.0..std::f32::consts::PI * 2.0);
+
+    remap(image, sampling, |x, y| {
+        let (dx, dy) = (x - cx, y - cy);
+        let distance = (dx * dx + dy * dy).sqrt();
+        if distance < f32::EPSILON {
+            return (x, y);
+        }
+        let shift = amplitude
+            * ((distance / wavelength) * std::f32::consts::PI * 2.0 + phase).sin()
+            / distance;
+        (x + dx * shift, y + dy * shift)
+    });
+}
+
+fn apply_fisheye(image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, level: u32, sampling: Sampling) {
+    if level == 0 {
+        return;
+    }
+    let cx = image.width() as f32 / 2.0;
+    let cy = image.height() as f32 / 2.0;
+    let max_radius = (cx * cx + cy * cy).sqrt();
+    let strength = level as f32 * 0.005;
+
+    remap(image, sampling, |x, y| {
+        let (dx, dy) = (x - cx, y - cy);
+        let r = (dx * dx + dy * dy).sqrt() / max_radius;
+        // Shrink sampling distance near the center, leaving the corners in place
+        let factor = 1.0 - strength * (1.0 - r * r);
+        (cx + dx * factor, cy + dy * factor)
+    });
+}
+
+fn apply_perspective(image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, level: u32, sampling: Sampling) {
+    if level == 0 {
+        return;
+    }
+    let mut rng = rng();
+    let width = image.width() as f32;
+    let height = image.height() as f32;
+    let max_dx = width * level as f32 * 0.002;
+    let max_dy = height * level as f32 * 0.002;
+
+    let corners = [(0.0, 0.0), (width, 0.0), (width, height), (0.0, height)];
+    let mut moved = corners;
+    for corner in moved.iter_mut() {
+        corner.0 += rng.random_range(-max_dx..=max_dx);
+        corner.1 += rng.random_range(-max_dy..=max_dy);
+    }
+
+    let Some(projection) = Projection::from_control_points(corners, moved) else {
+        return;
+    };
+    remap(image, sampling, |x, y| projection * (x, y));
+}
+
+fn apply_elastic(image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, level: u32, sampling: Sampling) {
+    if level == 0 {
+        return;
+    }
+    let width = image.width();
+    let height = image.height();
+    let alpha = level as f32 * 0.1;
+    let sigma = (width.min(height) as f32 / 10.0).clamp(2.0, 8.0);
+
+    let field_x = smooth_random_field(width, height, sigma);
+    let field_y = smooth_random_field(width, height, sigma);
+
+    remap(image, sampling, |x, y| {
+        let (px, py) = (x as u32, y as u32);
+        (
+            x + alpha * field_x.get_pixel(px, py)[0],
+            y + alpha * field_y.get_pixel(px, py)[0],
+        )
+    });
+}
+
+/// Uniform noise in `-1..1`, Gaussian-smoothed and rescaled back to `-1..1`.
+fn smooth_random_field(width: u32, height: u32, sigma: f32) -> ImageBuffer<Luma<f32>, Vec<f32>> {
+    let mut rng = rng();
+    let noise = ImageBuffer::from_fn(width, height, |_, _| {
+        Luma([rng.random_range(-1.0f32..=1.0)])
+    });
+    let mut field = gaussian_blur_f32(&noise, sigma);
+
+    let peak = field.iter().fold(0.0f32, |max, v| max.max(v.abs()));
+    if peak > f32::EPSILON {
+        field.iter_mut().for_each(|v| *v /= peak);
+    }
+    field
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient(width: u32, height: u32) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        ImageBuffer::from_fn(width, height, |x, y| {
+            Rgb([
+                (x * 7 % 256) as u8,
+                (y * 13 % 256) as u8,
+                ((x + y) % 256) as u8,
+            ])
+        })
+    }
+
+    #[test]
+    fn zero_level_leaves_the_image_untouched() {
+        let distortions = [
+            Distortion::Wavy(0),
+            Distortion::Swirl(0),
+            Distortion::Ripple(0),
+            Distortion::Fisheye(0),
+            Distortion::Perspective(0),
+            Distortion::Elastic(0),
+        ];
+        for distortion in distortions {
+            let mut image = gradient(120, 40);
+            distortion.apply(&mut image, Sampling::Nearest);
+            assert_eq!(image, gradient(120, 40), "{:?}", distortion);
+        }
+    }
+
+    #[test]
+    fn every_distortion_changes_the_image() {
+        let distortions = [
+            Distortion::Swirl(60),
+            Distortion::Ripple(60),
+            Distortion::Fisheye(60),
+            Distortion::Perspective(60),
+            Distortion::Elastic(60),
+        ];
+        for distortion in distortions {
+            for sampling in [Sampling::Nearest, Sampling::Bilinear] {
+                let mut image = gradient(120, 40);
+                distortion.apply(&mut image, sampling);
+                assert_eq!(image.dimensions(), (120, 40));
+                assert_ne!(image, gradient(120, 40), "{:?} {:?}", distortion, sampling);
+            }
+        }
+    }
+
+    #[test]
+    fn tiny_images_are_ignored() {
+        let mut image = gradient(1, 1);
+        Distortion::Elastic(100).apply(&mut image, Sampling::Bilinear);
+        assert_eq!(image, gradient(1, 1));
+    }
+}
@@ -1,3 +1,14 @@
+mod distortion;
+mod occlusion;
+mod random;
 mod standard;
+mod stroke;
+#[cfg(test)]
+mod wavy_reference;
 
+pub use distortion::*;
+pub use occlusion::*;
+pub use random::{RandomSource, set_random_source};
+pub(crate) use random::{rng, secure_rng, with_seed};
 pub use standard::*;
+pub use stroke::StrokeStyle;
@@ -0,0 +1,136 @@
+use image::{ImageBuffer, Rgb};
+use rand::Rng;
+
+use super::random::rng;
+use super::standard::{Glyph, get_color};
+use super::stroke::{cubic_bezier_points, draw_stroke, smooth_curve_points};
+
+/// Draw a curve through the glyph band, following the text from the first
+/// character to the last so it crosses every glyph.
+pub fn draw_strike_through(
+    image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>,
+    glyphs: &[Glyph],
+    dark_mode: bool,
+    antialias: bool,
+) {
+    let (Some(first), Some(last)) = (glyphs.first(), glyphs.last()) else {
+        return;
+    };
+    let mut rng = rng();
+    let size = first.size;
+
+    // Anchor points through every glyph center, jittered vertically
+    let mut points = Vec::with_capacity(glyphs.len() + 2);
+    let (start_x, start_y) = first.center();
+    points.push((start_x - first.width, start_y));
+    for glyph in glyphs {
+        let (x, y) = glyph.center();
+        let jitter = rng.random_range(-0.12..=0.12) * size;
+        points.push((x, y + jitter));
+    }
+    let (end_x, end_y) = last.center();
+    points.push((end_x + last.width, end_y));
+
+    let path = smooth_curve_points(&points, 12);
+    let width = variable_width(size * 0.05, size * 0.09);
+    draw_stroke(image, &path, width, get_color(dark_mode), antialias);
+}
+
+/// Draw `num` arcs, each joining two adjacent glyphs and using the color of one of
+/// them so the arc can't be separated from the text by color.
+pub fn draw_glyph_arcs(
+    image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>,
+    glyphs: &[Glyph],
+    num: usize,
+    antialias: bool,
+) {
+    if glyphs.is_empty() {
+        return;
+    }
+    let mut rng = rng();
+
+    for _ in 0..num {
+        let i = rng.random_range(0..glyphs.len());
+        let j = (i + 1).min(glyphs.len() - 1);
+        let (a, b) = (&glyphs[i], &glyphs[j]);
+        let color = if rng.random_bool(0.5) {
+            a.color
+        } else {
+            b.color
+        };
+
+        let (ax, ay) = a.center();
+        let (bx, by) = b.center();
+        let start = (
+            ax - a.width * 0.4,
+            ay + rng.random_range(-0.2..=0.2) * a.size,
+        );
+        let end = (
+            bx + b.width * 0.4,
+            by + rng.random_range(-0.2..=0.2) * b.size,
+        );
+
+        // Bend the arc up or down through the glyphs
+        let direction = if rng.random_bool(0.5) { 1.0 } else { -1.0 };
+        let bend = direction * rng.random_range(0.25..=0.5) * a.size;
+        let span = end.0 - start.0;
+        let control_a = (start.0 + span / 3.0, start.1 + bend);
+        let control_b = (start.0 + span * 2.0 / 3.0, end.1 + bend);
+
+        let path = cubic_bezier_points(start, control_a, control_b, end, 32);
+        let width = variable_width(a.size * 0.04, a.size * 0.08);
+        draw_stroke(image, &path, width, color, antialias);
+    }
+}
+
+/// A stroke width between `min` and `max`, swelling and tapering along the path.
+fn variable_width(min: f32, max: f32) -> impl Fn(f32) -> f32 {
+    let mut rng = rng();
+    let cycles: f32 = rng.random_range(0.5..=2.0);
+    let phase: f32 = rng.random_range(0.0..std::f32::consts::PI * 2.0);
+    move |t| {
+        let swell = 0.5 + 0.5 * (t * cycles * std::f32::consts::PI * 2.0 + phase).sin();
+        min + (max - min) * swell
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glyphs() -> Vec<Glyph> {
+        (0..5)
+            .map(|i| Glyph {
+                x: 5.0 + i as f32 * 40.0,
+                baseline: 50.0,
+                width: 25.0,
+                size: 42.0,
+                color: Rgb([214, 14, 50]),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn strike_through_crosses_every_glyph() {
+        let background = Rgb([224, 238, 253]);
+        let mut image = ImageBuffer::from_pixel(210, 70, background);
+        let glyphs = glyphs();
+        draw_strike_through(&mut image, &glyphs, false, false);
+
+        for glyph in &glyphs {
+            let x = glyph.center().0 as u32;
+            assert!((0..70).any(|y| *image.get_pixel(x, y) != background));
+        }
+    }
+
+    #[test]
+    fn arcs_use_glyph_colors() {
+        let background = Rgb([224, 238, 253]);
+        let mut image = ImageBuffer::from_pixel(210, 70, background);
+        draw_glyph_arcs(&mut image, &glyphs(), 4, false);
+
+        let painted: Vec<_> = image.pixels().filter(|p| **p != background).collect();
+        assert!(!painted.is_empty());
+        assert!(painted.iter().all(|p| **p == Rgb([214, 14, 50])));
+    }
+}
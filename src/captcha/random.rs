@@ -0,0 +1,129 @@
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use std::cell::RefCell;
+use std::sync::OnceLock;
+
+/// Fills a buffer with random bytes, see [`set_random_source`].
+pub type RandomSource = Box<dyn Fn(&mut [u8]) + Send + Sync>;
+
+static SOURCE: OnceLock<RandomSource> = OnceLock::new();
+
+thread_local! {
+    static SEEDED: RefCell<Option<StdRng>> = const { RefCell::new(None) };
+    /// Drawing generator seeded from [`SOURCE`], so images don't call it per pixel.
+    static SOURCED: RefCell<Option<StdRng>> = const { RefCell::new(None) };
+}
+
+/// Replace the operating system as the source of randomness, e.g. with
+/// `crypto.getRandomValues` on a WebAssembly runtime without `getrandom` support.
+///
+/// Token ids, salts and nonces are read from `source` directly. Drawing uses a
+/// generator seeded from it once per thread. Can only be set once, before the first
+/// captcha is generated. Returns `false` if a source was already set.
+pub fn set_random_source(source: RandomSource) -> bool {
+    SOURCE.set(source).is_ok()
+}
+
+/// Random source for secrets: the configured [`RandomSource`] or the OS-seeded
+/// thread-local generator.
+pub(crate) struct SecureRng;
+
+pub(crate) fn secure_rng() -> SecureRng {
+    SecureRng
+}
+
+impl RngCore for SecureRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dst: &mut [u8]) {
+        match SOURCE.get() {
+            Some(source) => source(dst),
+            None => rand::rng().fill_bytes(dst),
+        }
+    }
+}
+
+/// Random source used by every drawing routine.
+///
+/// Draws from the thread-local OS-seeded generator (or one seeded from the
+/// configured [`RandomSource`]), or from the seeded generator installed by
+/// [`with_seed`] on the current thread.
+pub(crate) struct DrawRng;
+
+pub(crate) fn rng() -> DrawRng {
+    DrawRng
+}
+
+impl DrawRng {
+    fn with<T>(&mut self, f: impl FnOnce(&mut dyn RngCore) -> T) -> T {
+        SEEDED.with(|seeded| match seeded.borrow_mut().as_mut() {
+            Some(seeded) => f(seeded),
+            None if SOURCE.get().is_some() => SOURCED.with(|sourced| {
+                let mut sourced = sourced.borrow_mut();
+                f(sourced.get_or_insert_with(|| StdRng::from_rng(&mut SecureRng)))
+            }),
+            None => f(&mut rand::rng()),
+        })
+    }
+}
+
+impl RngCore for DrawRng {
+    fn next_u32(&mut self) -> u32 {
+        self.with(|rng| rng.next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.with(|rng| rng.next_u64())
+    }
+
+    fn fill_bytes(&mut self, dst: &mut [u8]) {
+        self.with(|rng| rng.fill_bytes(dst))
+    }
+}
+
+/// Restores the previous generator when dropped, even if drawing panics.
+struct Restore(Option<StdRng>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        SEEDED.with(|seeded| *seeded.borrow_mut() = previous);
+    }
+}
+
+/// Run `f` with drawing randomness derived from `seed`, or unchanged if `None`.
+pub(crate) fn with_seed<T>(seed: Option<u64>, f: impl FnOnce() -> T) -> T {
+    let Some(seed) = seed else {
+        return f();
+    };
+    let previous = SEEDED.with(|seeded| seeded.replace(Some(StdRng::seed_from_u64(seed))));
+    let _restore = Restore(previous);
+    f()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rng, with_seed};
+    use rand::Rng;
+
+    #[test]
+    fn it_repeats_seeded_sequences() {
+        let draw = || (0..8).map(|_| rng().random::<u32>()).collect::<Vec<_>>();
+        let first = with_seed(Some(7), draw);
+        assert_eq!(first, with_seed(Some(7), draw));
+        assert_ne!(first, with_seed(Some(8), draw));
+
+        let nested = with_seed(Some(1), || with_seed(Some(7), draw));
+        assert_eq!(first, nested);
+    }
+}
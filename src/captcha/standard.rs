@@ -1,13 +1,21 @@
-use ab_glyph::FontArc;
+use ab_glyph::{Font, FontArc, ScaleFont};
 use base64::Engine;
 use base64::engine::general_purpose;
 use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
 use image::{DynamicImage, ImageBuffer, Rgb};
-use imageproc::drawing::{draw_cubic_bezier_curve_mut, draw_hollow_ellipse_mut, draw_text_mut};
-use rand::{Rng, rng};
+use imageproc::drawing::{
+    draw_cubic_bezier_curve_mut, draw_hollow_ellipse_mut, draw_text_mut, text_size,
+};
+use rand::Rng;
 use std::io::Cursor;
 use std::sync::OnceLock;
 
+use super::random::rng;
+use super::stroke::{
+    StrokeStyle, cubic_bezier_points, draw_stroke, ellipse_arc_points, fill_disc, fill_ellipse,
+};
+
 // ==========================================
 // CONSTANTS
 // ==========================================
@@ -118,15 +126,36 @@ pub fn get_image(width: u32, height: u32, dark_mode: bool) -> ImageBuffer<Rgb<u8
 // DRAWING ROUTINES
 // ==========================================
 
+/// Position, size and color of a character drawn by [`cyclic_write_character`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Glyph {
+    pub x: f32,
+    pub baseline: f32,
+    pub width: f32,
+    pub size: f32,
+    pub color: Rgb<u8>,
+}
+
+impl Glyph {
+    /// Middle of the glyph band, halfway between the baseline and the x-height.
+    pub fn center(&self) -> (f32, f32) {
+        (self.x + self.width / 2.0, self.baseline - self.size * 0.27)
+    }
+}
+
 /// Write the captcha characters on the background image in a layout.
+///
+/// Each character uses a random font of `fonts`, or the embedded font if empty.
+/// Returns the layout of every drawn character so later passes can target the text.
 pub fn cyclic_write_character(
     res: &[String],
     image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>,
     dark_mode: bool,
     drop_shadow: bool,
-) {
+    fonts: &[FontArc],
+) -> Vec<Glyph> {
     if res.is_empty() {
-        return;
+        return Vec::new();
     }
 
     let usable_width = image.width().saturating_sub(10);
@@ -139,10 +168,18 @@ pub fn cyclic_write_character(
         _ => SCALE_SM,
     };
 
-    let font = get_font();
+    let default_font = [get_font()];
+    let fonts = if fonts.is_empty() {
+        &default_font[..]
+    } else {
+        fonts
+    };
+    let mut glyphs = Vec::with_capacity(res.len());
 
     for (i, _) in res.iter().enumerate() {
         let text = &res[i];
+        let font = &fonts[get_rnd(fonts.len() - 1)];
+        let ascent = font.as_scaled(scale).ascent();
         let color = get_color(dark_mode);
         let x = 5 + (i as u32 * c) as i32;
 
@@ -154,17 +191,31 @@ pub fn cyclic_write_character(
                 x + 2,
                 y as i32 + 2,
                 scale,
-                &font,
+                font,
                 text,
             );
         }
 
-        draw_text_mut(image, color, x, y as i32, scale, &font, text);
+        draw_text_mut(image, color, x, y as i32, scale, font, text);
+
+        glyphs.push(Glyph {
+            x: x as f32,
+            baseline: y as f32 + ascent,
+            width: text_size(scale, font, text).0 as f32,
+            size: scale,
+            color,
+        });
     }
+
+    glyphs
 }
 
 /// Draw a random interference line (bezier curve) on the background picture.
-pub fn draw_interference_line(image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, dark_mode: bool) {
+pub fn draw_interference_line(
+    image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>,
+    dark_mode: bool,
+    style: StrokeStyle,
+) {
     let width = image.width();
     let height = image.height();
     if width <= 5 || height <= 5 {
@@ -183,22 +234,38 @@ pub fn draw_interference_line(image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, dark_mo
     let ctrl_x2 = get_next((width / 4) as f32, width / 4 * 3);
     let ctrl_y2 = get_next(x1, height - 5);
 
+    let color = get_color(dark_mode);
+
     // Randomly draw bezier curves
-    draw_cubic_bezier_curve_mut(
-        image,
+    if style.is_hairline() {
+        draw_cubic_bezier_curve_mut(
+            image,
+            (x1, y1),
+            (x2, y2),
+            (ctrl_x, ctrl_y),
+            (ctrl_x2, ctrl_y2),
+            color,
+        );
+        return;
+    }
+
+    let path = cubic_bezier_points(
         (x1, y1),
-        (x2, y2),
         (ctrl_x, ctrl_y),
         (ctrl_x2, ctrl_y2),
-        get_color(dark_mode),
+        (x2, y2),
+        width as usize / 2,
     );
+    draw_stroke(image, &path, |_| style.width, color, style.antialias);
 }
 
-/// Draw a distraction circle (hollow ellipse) in random positions.
+/// Draw a distraction circle (hollow or filled ellipse) in random positions.
 pub fn draw_interference_ellipse(
     num: usize,
     image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>,
     dark_mode: bool,
+    style: StrokeStyle,
+    filled: bool,
 ) {
     if image.width() <= 25 || image.height() <= 15 {
         return;
@@ -207,7 +274,74 @@ pub fn draw_interference_ellipse(
         let w = (10 + get_rnd(5)) as i32;
         let x = get_rnd((image.width() - 25) as usize) as i32;
         let y = get_rnd((image.height() - 15) as usize) as i32;
-        draw_hollow_ellipse_mut(image, (x, y), w, w, get_color(dark_mode));
+        let color = get_color(dark_mode);
+
+        let center = (x as f32, y as f32);
+        let radii = (w as f32, w as f32);
+        if filled {
+            fill_ellipse(image, center, radii, color, style.antialias);
+        } else if style.is_hairline() {
+            draw_hollow_ellipse_mut(image, (x, y), w, w, color);
+        } else {
+            let path = ellipse_arc_points(center, radii, 0.0, std::f32::consts::PI * 2.0);
+            draw_stroke(image, &path, |_| style.width, color, style.antialias);
+        }
+    }
+}
+
+/// Draw random distraction shapes: polygon outlines, open arcs and clusters of dots.
+pub fn draw_interference_shapes(
+    num: usize,
+    image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>,
+    dark_mode: bool,
+    style: StrokeStyle,
+) {
+    let width = image.width() as f32;
+    let height = image.height() as f32;
+    if width <= 25.0 || height <= 15.0 {
+        return;
+    }
+    let mut rng = rng();
+
+    for _ in 0..num {
+        let center = (rng.random_range(0.0..width), rng.random_range(0.0..height));
+        let radius: f32 = rng.random_range(6.0..=15.0);
+        let color = get_color(dark_mode);
+
+        match rng.random_range(0..3) {
+            0 => {
+                // Closed polygon with 3 to 6 jittered vertices
+                let sides = rng.random_range(3..=6);
+                let rotation: f32 = rng.random_range(0.0..std::f32::consts::PI * 2.0);
+                let mut path: Vec<(f32, f32)> = (0..sides)
+                    .map(|i| {
+                        let angle = rotation + std::f32::consts::PI * 2.0 * i as f32 / sides as f32;
+                        let r = radius * rng.random_range(0.7..=1.0);
+                        (center.0 + r * angle.cos(), center.1 + r * angle.sin())
+                    })
+                    .collect();
+                path.push(path[0]);
+                draw_stroke(image, &path, |_| style.width, color, style.antialias);
+            }
+            1 => {
+                let start: f32 = rng.random_range(0.0..std::f32::consts::PI * 2.0);
+                let sweep: f32 = rng.random_range(1.0..=4.0);
+                let radii = (radius, radius * rng.random_range(0.5..=1.0));
+                let path = ellipse_arc_points(center, radii, start, start + sweep);
+                draw_stroke(image, &path, |_| style.width, color, style.antialias);
+            }
+            _ => {
+                let dots = rng.random_range(3..=8);
+                for _ in 0..dots {
+                    let dot = (
+                        center.0 + rng.random_range(-radius..=radius),
+                        center.1 + rng.random_range(-radius..=radius),
+                    );
+                    let dot_radius = style.width.max(1.0) * rng.random_range(0.75..=1.5);
+                    fill_disc(image, dot, dot_radius, color, style.antialias);
+                }
+            }
+        }
     }
 }
 
@@ -215,52 +349,297 @@ pub fn draw_interference_ellipse(
 // EFFECTS
 // ==========================================
 
+/// Pixel sampling used when a distortion maps an output pixel back to the source image.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Sampling {
+    /// Copy the closest source pixel. Fastest, with slightly jagged edges.
+    #[default]
+    Nearest,
+    /// Blend the four surrounding source pixels for smoother output.
+    Bilinear,
+}
+
+/// Parameters of the sine wave used by [`apply_wavy_distortion`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Wave {
+    pub amplitude: f32,
+    pub frequency: f32,
+    pub phase: f32,
+}
+
+impl Wave {
+    /// Randomize the wave phase and frequency slightly for the given level.
+    fn random(level: u32) -> Self {
+        let mut rng = rng();
+        let phase: f32 = rng.random_range(0.0..std::f32::consts::PI * 2.0);
+        // Amplitude is related to distortion level, capped for readability
+        let amplitude = (level as f32) * 1.5;
+        let frequency = 0.05 + (rng.random_range(0.0..0.05) * level as f32);
+        Wave {
+            amplitude,
+            frequency,
+            phase,
+        }
+    }
+
+    /// Horizontal displacement of every row; it only depends on `y`.
+    fn row_offsets(&self, height: u32) -> Vec<f32> {
+        (0..height)
+            .map(|y| self.amplitude * ((y as f32 * self.frequency) + self.phase).sin())
+            .collect()
+    }
+
+    /// Vertical displacement of every column; it only depends on `x`.
+    fn column_offsets(&self, width: u32) -> Vec<f32> {
+        (0..width)
+            .map(|x| self.amplitude * ((x as f32 * self.frequency) + self.phase).cos())
+            .collect()
+    }
+}
+
 /// Apply wavy pixel-level distortion to the image to deter OCR bots.
-pub fn apply_wavy_distortion(image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, level: u32) {
+pub(crate) fn apply_wavy_distortion(
+    image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>,
+    level: u32,
+    sampling: Sampling,
+) {
     if level == 0 {
         return;
     }
+    let wave = Wave::random(level);
+    match sampling {
+        Sampling::Nearest => wavy_nearest(image, &wave),
+        Sampling::Bilinear => wavy_bilinear(image, &wave),
+    }
+}
+
+/// Nearest-neighbour wave using precomputed offset tables.
+///
+/// Columns sharing the same vertical offset read from the same source row, so each
+/// such run is copied as one slice instead of pixel by pixel.
+pub(crate) fn wavy_nearest(image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, wave: &Wave) {
+    let width = image.width() as usize;
+    let height = image.height() as usize;
+    if width == 0 || height == 0 {
+        return;
+    }
+
+    let offsets_x: Vec<isize> = wave
+        .row_offsets(height as u32)
+        .into_iter()
+        .map(|o| o as i32 as isize)
+        .collect();
+
+    // Group consecutive columns with the same vertical offset: (start, end, offset_y)
+    let mut runs: Vec<(usize, usize, isize)> = Vec::new();
+    for (x, o) in wave.column_offsets(width as u32).into_iter().enumerate() {
+        let offset_y = o as i32 as isize;
+        match runs.last_mut() {
+            Some((_, end, last)) if *last == offset_y => *end = x + 1,
+            _ => runs.push((x, x + 1, offset_y)),
+        }
+    }
+
+    let stride = width * 3;
+    let src: &[u8] = image.as_raw();
+    let mut out = vec![0u8; src.len()];
+
+    for (y, dst_row) in out.chunks_exact_mut(stride).enumerate() {
+        let offset_x = offsets_x[y];
+        for &(start, end, offset_y) in &runs {
+            let src_y = (y as isize + offset_y).clamp(0, height as isize - 1) as usize;
+            let src_row = &src[src_y * stride..(src_y + 1) * stride];
+            copy_shifted(dst_row, src_row, start, end, offset_x);
+        }
+    }
 
+    image.copy_from_slice(&out);
+}
+
+/// Copy columns `start..end` of `src_row` shifted by `offset` into `dst_row`,
+/// repeating the edge pixels where the shift runs off the image.
+fn copy_shifted(dst_row: &mut [u8], src_row: &[u8], start: usize, end: usize, offset: isize) {
+    let width = (src_row.len() / 3) as isize;
+    let (start, end) = (start as isize, end as isize);
+
+    let left = (-offset).clamp(start, end);
+    let right = (width - offset).clamp(left, end);
+
+    for x in start..left {
+        dst_row[x as usize * 3..x as usize * 3 + 3].copy_from_slice(&src_row[..3]);
+    }
+    if left < right {
+        let dst = &mut dst_row[left as usize * 3..right as usize * 3];
+        dst.copy_from_slice(&src_row[(left + offset) as usize * 3..(right + offset) as usize * 3]);
+    }
+    let last = src_row.len() - 3;
+    for x in right..end {
+        dst_row[x as usize * 3..x as usize * 3 + 3].copy_from_slice(&src_row[last..]);
+    }
+}
+
+/// Wave with sub-pixel offsets, blending neighbouring source pixels.
+pub(crate) fn wavy_bilinear(image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, wave: &Wave) {
     let width = image.width();
     let height = image.height();
-    let mut new_image = image.clone();
-    let mut rng = rng();
-
-    // Randomize the wave phase and frequency slightly
-    let phase: f32 = rng.random_range(0.0..std::f32::consts::PI * 2.0);
-    // Amplitude is related to distortion level, capped for readability
-    let amplitude = (level as f32) * 1.5;
-    let frequency = 0.05 + (rng.random_range(0.0..0.05) * level as f32);
+    if width == 0 || height == 0 {
+        return;
+    }
 
-    for y in 0..height {
-        for x in 0..width {
-            // Calculate pixel displacement using a sine wave
-            let offset_x = (amplitude * ((y as f32 * frequency) + phase).sin()) as i32;
-            let offset_y = (amplitude * ((x as f32 * frequency) + phase).cos()) as i32;
+    let offsets_x = wave.row_offsets(height);
+    let offsets_y = wave.column_offsets(width);
 
-            let src_x = (x as i32 + offset_x).clamp(0, width as i32 - 1) as u32;
-            let src_y = (y as i32 + offset_y).clamp(0, height as i32 - 1) as u32;
+    let stride = width as usize * 3;
+    let src: &[u8] = image.as_raw();
+    let mut out = vec![0u8; src.len()];
 
-            // Map the source pixel to the destination
-            let pixel = image.get_pixel(src_x, src_y);
-            new_image.put_pixel(x, y, *pixel);
+    for (y, dst_row) in out.chunks_exact_mut(stride).enumerate() {
+        let offset_x = offsets_x[y];
+        for (x, dst) in dst_row.chunks_exact_mut(3).enumerate() {
+            let src_x = x as f32 + offset_x;
+            let src_y = y as f32 + offsets_y[x];
+            dst.copy_from_slice(&sample_bilinear(src, width, height, src_x, src_y));
         }
     }
 
-    *image = new_image;
+    image.copy_from_slice(&out);
+}
+
+/// Sample an RGB buffer at a fractional position, clamping to the image edges.
+pub(crate) fn sample_bilinear(src: &[u8], width: u32, height: u32, x: f32, y: f32) -> [u8; 3] {
+    let max_x = (width - 1) as f32;
+    let max_y = (height - 1) as f32;
+    let x = x.clamp(0.0, max_x);
+    let y = y.clamp(0.0, max_y);
+
+    let x0 = x as usize;
+    let y0 = y as usize;
+    let x1 = (x0 + 1).min(width as usize - 1);
+    let y1 = (y0 + 1).min(height as usize - 1);
+    // 8-bit fixed-point weights keep the blend in integer arithmetic
+    let fx = ((x - x0 as f32) * 256.0) as u32;
+    let fy = ((y - y0 as f32) * 256.0) as u32;
+
+    let stride = width as usize * 3;
+    let row0 = &src[y0 * stride..y0 * stride + stride];
+    let row1 = &src[y1 * stride..y1 * stride + stride];
+    let (a, b) = (&row0[x0 * 3..x0 * 3 + 3], &row0[x1 * 3..x1 * 3 + 3]);
+    let (c, d) = (&row1[x0 * 3..x0 * 3 + 3], &row1[x1 * 3..x1 * 3 + 3]);
+
+    let mut pixel = [0u8; 3];
+    for i in 0..3 {
+        let top = a[i] as u32 * (256 - fx) + b[i] as u32 * fx;
+        let bottom = c[i] as u32 * (256 - fx) + d[i] as u32 * fx;
+        pixel[i] = ((top * (256 - fy) + bottom * fy + (1 << 15)) >> 16) as u8;
+    }
+    pixel
 }
 
 // ==========================================
 // EXPORT & CONVERSION
 // ==========================================
 
-/// Convert a `DynamicImage` to a JPEG base64 Data URI string.
-pub fn to_base64_str(image: &DynamicImage, compression: u8) -> String {
+/// Encoding of the captcha image.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum OutputFormat {
+    /// Lossy JPEG using the builder's `compression` as quality.
+    #[default]
+    Jpeg,
+    /// Lossless PNG, larger but without compression artifacts.
+    Png,
+}
+
+impl OutputFormat {
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            OutputFormat::Jpeg => "image/jpeg",
+            OutputFormat::Png => "image/png",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Png => "png",
+        }
+    }
+}
+
+/// Encode a `DynamicImage` in the given format. Returns an empty buffer on failure.
+pub fn encode_image(image: &DynamicImage, compression: u8, format: OutputFormat) -> Vec<u8> {
     let mut buf = Cursor::new(Vec::new());
-    let mut encoder = JpegEncoder::new_with_quality(&mut buf, compression);
-    if encoder.encode_image(image).is_err() {
-        return "data:image/jpeg;base64,".to_string();
+    let result = match format {
+        OutputFormat::Jpeg => {
+            JpegEncoder::new_with_quality(&mut buf, compression).encode_image(image)
+        }
+        OutputFormat::Png => image.write_with_encoder(PngEncoder::new(&mut buf)),
+    };
+    if result.is_err() {
+        return Vec::new();
+    }
+    buf.into_inner()
+}
+
+/// Convert a `DynamicImage` to a base64 Data URI string.
+pub fn to_base64_str(image: &DynamicImage, compression: u8, format: OutputFormat) -> String {
+    let res_base64 = general_purpose::STANDARD.encode(encode_image(image, compression, format));
+    format!("data:{};base64,{}", format.mime_type(), res_base64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::captcha::wavy_reference::reference_wavy;
+
+    fn gradient(width: u32, height: u32) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        ImageBuffer::from_fn(width, height, |x, y| {
+            Rgb([
+                (x * 7 % 256) as u8,
+                (y * 13 % 256) as u8,
+                ((x + y) % 256) as u8,
+            ])
+        })
+    }
+
+    #[test]
+    fn nearest_wave_matches_per_pixel_reference() {
+        for level in [1, 5, 20, 100] {
+            let wave = Wave {
+                amplitude: level as f32 * 1.5,
+                frequency: 0.05 + 0.03 * level as f32,
+                phase: 1.3,
+            };
+            let image = gradient(200, 70);
+            let expected = reference_wavy(&image, wave.amplitude, wave.frequency, wave.phase);
+
+            let mut actual = image.clone();
+            wavy_nearest(&mut actual, &wave);
+            assert_eq!(actual, expected, "level {}", level);
+        }
+    }
+
+    #[test]
+    fn bilinear_wave_with_integer_offsets_is_a_plain_copy() {
+        let wave = Wave {
+            amplitude: 0.0,
+            frequency: 0.1,
+            phase: 0.0,
+        };
+        let image = gradient(64, 32);
+        let mut actual = image.clone();
+        wavy_bilinear(&mut actual, &wave);
+        assert_eq!(actual, image);
+    }
+
+    #[test]
+    fn bilinear_sample_blends_neighbours() {
+        let src = [0, 0, 0, 100, 200, 50];
+        assert_eq!(sample_bilinear(&src, 2, 1, 0.5, 0.0), [50, 100, 25]);
+        assert_eq!(sample_bilinear(&src, 2, 1, -4.0, 9.0), [0, 0, 0]);
+        assert_eq!(sample_bilinear(&src, 2, 1, 7.0, 0.0), [100, 200, 50]);
     }
-    let res_base64 = general_purpose::STANDARD.encode(buf.into_inner());
-    format!("data:image/jpeg;base64,{}", res_base64)
 }
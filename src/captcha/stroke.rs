@@ -0,0 +1,362 @@
+use image::{ImageBuffer, Rgb};
+
+/// Sample `steps + 1` points along a cubic Bézier curve.
+pub fn cubic_bezier_points(
+    start: (f32, f32),
+    control_a: (f32, f32),
+    control_b: (f32, f32),
+    end: (f32, f32),
+    steps: usize,
+) -> Vec<(f32, f32)> {
+    let steps = steps.max(1);
+    (0..=steps)
+        .map(|i| {
+            let t = i as f32 / steps as f32;
+            let u = 1.0 - t;
+            let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+            (
+                a * start.0 + b * control_a.0 + c * control_b.0 + d * end.0,
+                a * start.1 + b * control_a.1 + c * control_b.1 + d * end.1,
+            )
+        })
+        .collect()
+}
+
+/// Sample a smooth (Catmull-Rom) curve passing through every point.
+pub fn smooth_curve_points(points: &[(f32, f32)], steps_per_segment: usize) -> Vec<(f32, f32)> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+    let steps = steps_per_segment.max(1);
+    let last = points.len() - 1;
+    let mut curve = Vec::with_capacity(last * steps + 1);
+
+    for i in 0..last {
+        let p0 = points[i.saturating_sub(1)];
+        let (p1, p2) = (points[i], points[i + 1]);
+        let p3 = points[(i + 2).min(last)];
+        for step in 0..steps {
+            let t = step as f32 / steps as f32;
+            let (t2, t3) = (t * t, t * t * t);
+            let blend = |a: f32, b: f32, c: f32, d: f32| {
+                0.5 * ((2.0 * b)
+                    + (c - a) * t
+                    + (2.0 * a - 5.0 * b + 4.0 * c - d) * t2
+                    + (3.0 * b - a - 3.0 * c + d) * t3)
+            };
+            curve.push((blend(p0.0, p1.0, p2.0, p3.0), blend(p0.1, p1.1, p2.1, p3.1)));
+        }
+    }
+    curve.push(points[last]);
+    curve
+}
+
+/// How interference lines and outlines are stroked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StrokeStyle {
+    /// Stroke width in pixels.
+    pub width: f32,
+    /// Blend the stroke edges into the background instead of hard pixel steps.
+    pub antialias: bool,
+}
+
+impl StrokeStyle {
+    /// Whether this is the classic 1px aliased stroke.
+    pub fn is_hairline(&self) -> bool {
+        self.width <= 1.0 && !self.antialias
+    }
+}
+
+impl Default for StrokeStyle {
+    fn default() -> Self {
+        StrokeStyle {
+            width: 1.0,
+            antialias: false,
+        }
+    }
+}
+
+/// Per-pixel coverage over a clipped region of the image.
+///
+/// Shapes record their coverage here and the color is blended once at the end, so
+/// overlapping stamps of an anti-aliased stroke don't darken its edges.
+struct Coverage {
+    x0: i32,
+    y0: i32,
+    width: i32,
+    height: i32,
+    values: Vec<f32>,
+}
+
+impl Coverage {
+    /// Coverage for the region `min..=max`, clipped to the image.
+    fn new(
+        image: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+        min: (f32, f32),
+        max: (f32, f32),
+    ) -> Option<Self> {
+        let x0 = (min.0.floor() as i32).max(0);
+        let y0 = (min.1.floor() as i32).max(0);
+        let x1 = (max.0.ceil() as i32).min(image.width() as i32 - 1);
+        let y1 = (max.1.ceil() as i32).min(image.height() as i32 - 1);
+        if x1 < x0 || y1 < y0 {
+            return None;
+        }
+        let (width, height) = (x1 - x0 + 1, y1 - y0 + 1);
+        Some(Coverage {
+            x0,
+            y0,
+            width,
+            height,
+            values: vec![0.0; (width * height) as usize],
+        })
+    }
+
+    /// Record the coverage returned by `shape` for every pixel center in the bounds.
+    fn add<F>(&mut self, min: (f32, f32), max: (f32, f32), shape: F)
+    where
+        F: Fn(f32, f32) -> f32,
+    {
+        let min_x = (min.0.floor() as i32).max(self.x0);
+        let min_y = (min.1.floor() as i32).max(self.y0);
+        let max_x = (max.0.ceil() as i32).min(self.x0 + self.width - 1);
+        let max_y = (max.1.ceil() as i32).min(self.y0 + self.height - 1);
+
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let value = shape(x as f32 + 0.5, y as f32 + 0.5);
+                if value > 0.0 {
+                    let idx = ((y - self.y0) * self.width + (x - self.x0)) as usize;
+                    self.values[idx] = self.values[idx].max(value.min(1.0));
+                }
+            }
+        }
+    }
+
+    fn add_disc(&mut self, center: (f32, f32), radius: f32, antialias: bool) {
+        let radius = radius.max(0.5);
+        let reach = radius + 1.0;
+        self.add(
+            (center.0 - reach, center.1 - reach),
+            (center.0 + reach, center.1 + reach),
+            |x, y| {
+                let distance = (x - center.0).hypot(y - center.1);
+                edge_coverage(radius - distance, antialias)
+            },
+        );
+    }
+
+    fn blend(self, image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, color: Rgb<u8>) {
+        for (i, &value) in self.values.iter().enumerate() {
+            if value <= 0.0 {
+                continue;
+            }
+            let x = (self.x0 + i as i32 % self.width) as u32;
+            let y = (self.y0 + i as i32 / self.width) as u32;
+            let pixel = image.get_pixel_mut(x, y);
+            for c in 0..3 {
+                let base = pixel[c] as f32;
+                pixel[c] = (base + (color[c] as f32 - base) * value).round() as u8;
+            }
+        }
+    }
+}
+
+/// Coverage of a pixel whose center lies `inside` pixels within a shape edge.
+fn edge_coverage(inside: f32, antialias: bool) -> f32 {
+    if antialias {
+        (inside + 0.5).clamp(0.0, 1.0)
+    } else if inside >= 0.0 {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+/// Draw a path whose width varies along it.
+///
+/// `width` receives the relative position along the path (`0.0..=1.0`) and returns
+/// the stroke width in pixels at that point.
+pub fn draw_stroke<F>(
+    image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>,
+    path: &[(f32, f32)],
+    width: F,
+    color: Rgb<u8>,
+    antialias: bool,
+) where
+    F: Fn(f32) -> f32,
+{
+    if path.is_empty() {
+        return;
+    }
+    let total: f32 = path
+        .windows(2)
+        .map(|w| (w[1].0 - w[0].0).hypot(w[1].1 - w[0].1))
+        .sum();
+
+    // Bounds of the path, widened by the thickest point of the stroke
+    let reach = (0..=8)
+        .map(|i| width(i as f32 / 8.0))
+        .fold(0.0f32, f32::max)
+        / 2.0
+        + 2.0;
+    let (mut min, mut max) = (path[0], path[0]);
+    for &(x, y) in path {
+        min = (min.0.min(x), min.1.min(y));
+        max = (max.0.max(x), max.1.max(y));
+    }
+    let Some(mut coverage) = Coverage::new(
+        image,
+        (min.0 - reach, min.1 - reach),
+        (max.0 + reach, max.1 + reach),
+    ) else {
+        return;
+    };
+
+    // Stamp discs along the path, close enough together to form a solid stroke
+    let mut travelled = 0.0;
+    for segment in path.windows(2) {
+        let (from, to) = (segment[0], segment[1]);
+        let length = (to.0 - from.0).hypot(to.1 - from.1);
+        let mut offset = 0.0;
+        while offset < length {
+            let t = (travelled + offset) / total;
+            let radius = width(t) / 2.0;
+            let k = offset / length;
+            let point = (from.0 + (to.0 - from.0) * k, from.1 + (to.1 - from.1) * k);
+            coverage.add_disc(point, radius, antialias);
+            offset += (radius * 0.5).clamp(0.25, 0.5);
+        }
+        travelled += length;
+    }
+    let end = path[path.len() - 1];
+    coverage.add_disc(end, width(1.0) / 2.0, antialias);
+
+    coverage.blend(image, color);
+}
+
+/// Fill a disc of the given radius, always covering at least the center pixel.
+pub fn fill_disc(
+    image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>,
+    center: (f32, f32),
+    radius: f32,
+    color: Rgb<u8>,
+    antialias: bool,
+) {
+    let reach = radius.max(0.5) + 1.0;
+    let Some(mut coverage) = Coverage::new(
+        image,
+        (center.0 - reach, center.1 - reach),
+        (center.0 + reach, center.1 + reach),
+    ) else {
+        return;
+    };
+    coverage.add_disc(center, radius, antialias);
+    coverage.blend(image, color);
+}
+
+/// Fill an axis-aligned ellipse.
+pub fn fill_ellipse(
+    image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>,
+    center: (f32, f32),
+    radii: (f32, f32),
+    color: Rgb<u8>,
+    antialias: bool,
+) {
+    let (rx, ry) = (radii.0.max(0.5), radii.1.max(0.5));
+    let min = (center.0 - rx - 1.0, center.1 - ry - 1.0);
+    let max = (center.0 + rx + 1.0, center.1 + ry + 1.0);
+    let Some(mut coverage) = Coverage::new(image, min, max) else {
+        return;
+    };
+    coverage.add(min, max, |x, y| {
+        let distance = ((x - center.0) / rx).hypot((y - center.1) / ry);
+        // Approximate distance to the edge in pixels
+        edge_coverage((1.0 - distance) * rx.min(ry), antialias)
+    });
+    coverage.blend(image, color);
+}
+
+/// Sample points along an elliptical arc between two angles (in radians).
+pub fn ellipse_arc_points(
+    center: (f32, f32),
+    radii: (f32, f32),
+    start: f32,
+    end: f32,
+) -> Vec<(f32, f32)> {
+    let sweep = end - start;
+    let steps = ((radii.0.max(radii.1) * sweep.abs()) as usize).clamp(8, 256);
+    (0..=steps)
+        .map(|i| {
+            let angle = start + sweep * i as f32 / steps as f32;
+            (
+                center.0 + radii.0 * angle.cos(),
+                center.1 + radii.1 * angle.sin(),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smooth_curve_passes_through_points() {
+        let points = [(0.0, 0.0), (10.0, 5.0), (20.0, -5.0), (30.0, 0.0)];
+        let curve = smooth_curve_points(&points, 8);
+        for point in points {
+            assert!(curve.contains(&point));
+        }
+    }
+
+    #[test]
+    fn stroke_width_is_respected() {
+        let mut image = ImageBuffer::from_pixel(40, 20, Rgb([255, 255, 255]));
+        let path = [(5.0, 10.0), (35.0, 10.0)];
+        draw_stroke(&mut image, &path, |_| 5.0, Rgb([0, 0, 0]), false);
+
+        let painted = |y: u32| image.get_pixel(20, y)[0] == 0;
+        assert!(painted(8) && painted(10) && painted(11));
+        assert!(!painted(5) && !painted(15));
+    }
+
+    #[test]
+    fn stroke_outside_the_image_is_clipped() {
+        let mut image = ImageBuffer::from_pixel(10, 10, Rgb([255, 255, 255]));
+        draw_stroke(
+            &mut image,
+            &[(-50.0, -50.0), (60.0, 60.0)],
+            |_| 30.0,
+            Rgb([0, 0, 0]),
+            false,
+        );
+        assert_eq!(image.get_pixel(5, 5)[0], 0);
+    }
+
+    #[test]
+    fn antialiased_stroke_blends_its_edges() {
+        let mut image = ImageBuffer::from_pixel(40, 20, Rgb([255, 255, 255]));
+        draw_stroke(
+            &mut image,
+            &[(5.0, 10.3), (35.0, 10.3)],
+            |_| 3.0,
+            Rgb([0, 0, 0]),
+            true,
+        );
+
+        let column: Vec<u8> = (0..20).map(|y| image.get_pixel(20, y)[0]).collect();
+        assert_eq!(column[10], 0);
+        assert!(column.iter().any(|&v| v > 0 && v < 255));
+    }
+
+    #[test]
+    fn filled_ellipse_covers_its_center_only() {
+        let mut image = ImageBuffer::from_pixel(40, 40, Rgb([255, 255, 255]));
+        fill_ellipse(&mut image, (20.0, 20.0), (8.0, 4.0), Rgb([0, 0, 0]), false);
+        assert_eq!(image.get_pixel(20, 20)[0], 0);
+        assert_eq!(image.get_pixel(26, 20)[0], 0);
+        assert_eq!(image.get_pixel(20, 26)[0], 255);
+        assert_eq!(image.get_pixel(30, 20)[0], 255);
+    }
+}
@@ -0,0 +1,25 @@
+//! The original per-pixel wavy distortion, kept as the reference for the
+//! table-driven pass. Shared by the unit tests and the benchmarks.
+
+use image::{ImageBuffer, Rgb};
+
+/// Distort `image` with a sine wave of the given parameters, one pixel at a time.
+pub fn reference_wavy(
+    image: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    amplitude: f32,
+    frequency: f32,
+    phase: f32,
+) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    let (width, height) = image.dimensions();
+    let mut new_image = image.clone();
+    for y in 0..height {
+        for x in 0..width {
+            let offset_x = (amplitude * ((y as f32 * frequency) + phase).sin()) as i32;
+            let offset_y = (amplitude * ((x as f32 * frequency) + phase).cos()) as i32;
+            let src_x = (x as i32 + offset_x).clamp(0, width as i32 - 1) as u32;
+            let src_y = (y as i32 + offset_y).clamp(0, height as i32 - 1) as u32;
+            new_image.put_pixel(x, y, *image.get_pixel(src_x, src_y));
+        }
+    }
+    new_image
+}
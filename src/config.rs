@@ -0,0 +1,219 @@
+use std::io;
+use std::path::PathBuf;
+
+use ab_glyph::FontArc;
+use serde::{Deserialize, Serialize};
+
+use crate::captcha::BASIC_CHAR;
+use crate::{CaptchaBuilder, Distortion, MatchPolicy, OutputFormat, Sampling};
+
+/// Serializable settings for a [`CaptchaBuilder`], e.g. loaded from a TOML or JSON file.
+///
+/// Missing fields take the builder defaults. Values go through the builder setters,
+/// so they are clamped exactly like when configuring the builder in code.
+///
+/// ```rust
+/// use captcha_rs::CaptchaConfig;
+///
+/// let config: CaptchaConfig = serde_json::from_str(r#"{"length": 6, "dark_mode": true}"#).unwrap();
+/// let captcha = config.builder().unwrap().build();
+/// assert_eq!(captcha.text.chars().count(), 6);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CaptchaConfig {
+    pub length: usize,
+    /// Characters to draw from. Empty means the default set.
+    pub chars: String,
+    pub width: u32,
+    pub height: u32,
+    pub dark_mode: bool,
+    pub complexity: u32,
+    pub compression: u8,
+    pub drop_shadow: bool,
+    pub interference_lines: usize,
+    pub interference_ellipses: usize,
+    pub interference_shapes: usize,
+    pub stroke_width: u32,
+    pub antialias: bool,
+    pub filled_ellipses: bool,
+    pub distortion: u32,
+    pub distortions: Vec<Distortion>,
+    pub sampling: Sampling,
+    pub strike_through: bool,
+    pub glyph_arcs: usize,
+    pub match_policy: MatchPolicy,
+    /// TrueType or OpenType font files, loaded by [`CaptchaConfig::builder`].
+    pub fonts: Vec<PathBuf>,
+    pub format: OutputFormat,
+}
+
+impl Default for CaptchaConfig {
+    fn default() -> Self {
+        CaptchaConfig {
+            length: 5,
+            chars: BASIC_CHAR.iter().collect(),
+            width: 130,
+            height: 40,
+            dark_mode: false,
+            complexity: 1,
+            compression: 40,
+            drop_shadow: false,
+            interference_lines: 2,
+            interference_ellipses: 2,
+            interference_shapes: 0,
+            stroke_width: 1,
+            antialias: false,
+            filled_ellipses: false,
+            distortion: 0,
+            distortions: Vec::new(),
+            sampling: Sampling::Nearest,
+            strike_through: false,
+            glyph_arcs: 0,
+            match_policy: MatchPolicy::default(),
+            fonts: Vec::new(),
+            format: OutputFormat::Jpeg,
+        }
+    }
+}
+
+impl CaptchaConfig {
+    /// Create a builder with these settings, reading the font files.
+    ///
+    /// Fails if a font file can't be read or isn't a valid font.
+    pub fn builder(&self) -> io::Result<CaptchaBuilder> {
+        let mut builder = CaptchaBuilder::new()
+            .length(self.length)
+            .chars(self.chars.chars().collect())
+            .width(self.width)
+            .height(self.height)
+            .dark_mode(self.dark_mode)
+            .complexity(self.complexity)
+            .compression(self.compression)
+            .drop_shadow(self.drop_shadow)
+            .interference_lines(self.interference_lines)
+            .interference_ellipses(self.interference_ellipses)
+            .interference_shapes(self.interference_shapes)
+            .stroke_width(self.stroke_width)
+            .antialias(self.antialias)
+            .filled_ellipses(self.filled_ellipses)
+            .distortion(self.distortion)
+            .sampling(self.sampling)
+            .strike_through(self.strike_through)
+            .glyph_arcs(self.glyph_arcs)
+            .match_policy(self.match_policy.clone())
+            .format(self.format);
+
+        for distortion in &self.distortions {
+            builder = builder.add_distortion(*distortion);
+        }
+
+        for path in &self.fonts {
+            let data = std::fs::read(path)?;
+            let font = FontArc::try_from_vec(data).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid font file {}", path.display()),
+                )
+            })?;
+            builder = builder.font(font);
+        }
+
+        Ok(builder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CaptchaConfig;
+    use crate::{Distortion, MAX_CONFUSABLES, MatchPolicy, OutputFormat};
+    use std::io::ErrorKind;
+
+    #[test]
+    fn it_fills_missing_fields_with_builder_defaults() {
+        let config: CaptchaConfig = serde_json::from_str(
+            r#"{"width": 5000, "complexity": 0, "format": "png", "distortions": [{"swirl": 40}]}"#,
+        )
+        .unwrap();
+        assert_eq!(config.length, 5);
+        assert_eq!(config.distortions, vec![Distortion::Swirl(40)]);
+
+        let captcha = config.builder().unwrap().build();
+        assert_eq!(captcha.image.width(), 2000);
+        assert_eq!(captcha.text.len(), 5);
+        assert_eq!(captcha.format, OutputFormat::Png);
+        assert!(captcha.to_base64().starts_with("data:image/png;base64,"));
+    }
+
+    #[test]
+    fn it_round_trips_through_json() {
+        let config = CaptchaConfig {
+            dark_mode: true,
+            chars: String::from("AB"),
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&config).unwrap();
+        let parsed: CaptchaConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, config);
+
+        let captcha = parsed.builder().unwrap().build();
+        assert!(captcha.text.chars().all(|c| c == 'A' || c == 'B'));
+    }
+
+    #[test]
+    fn it_loads_fonts_by_path() {
+        let font = concat!(env!("CARGO_MANIFEST_DIR"), "/fonts/arial.ttf");
+        let config = CaptchaConfig {
+            fonts: vec![font.into()],
+            ..Default::default()
+        };
+        assert_eq!(config.builder().unwrap().build().text.len(), 5);
+
+        let config = CaptchaConfig {
+            fonts: vec![concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml").into()],
+            ..Default::default()
+        };
+        let err = config.builder().err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let config = CaptchaConfig {
+            fonts: vec!["does/not/exist.ttf".into()],
+            ..Default::default()
+        };
+        assert_eq!(config.builder().err().unwrap().kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn it_clamps_the_match_policy() {
+        let confusables: Vec<String> = (0..100).map(|_| String::from(r#"["a","b"]"#)).collect();
+        let json = format!(
+            r#"{{"match_policy": {{"max_edits": 5, "confusables": [{}]}}}}"#,
+            confusables.join(",")
+        );
+        let config: CaptchaConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(config.match_policy.max_edits(), 1);
+        assert_eq!(
+            config.match_policy,
+            (0..MAX_CONFUSABLES).fold(MatchPolicy::new().edit_distance(1), |policy, _| {
+                policy.confusable('a', 'b')
+            })
+        );
+
+        #[cfg(not(feature = "nfkc"))]
+        assert!(
+            serde_json::from_str::<CaptchaConfig>(r#"{"match_policy": {"nfkc": true}}"#).is_err()
+        );
+
+        // Tokens issued with a loaded policy verify
+        #[cfg(feature = "stateless")]
+        {
+            let captcha = config
+                .builder()
+                .unwrap()
+                .text(String::from("abcde"))
+                .build();
+            let token = captcha.as_token("config-secret", 60).unwrap();
+            assert_eq!(crate::verify(&token, "abcd", "config-secret"), Some(true));
+        }
+    }
+}
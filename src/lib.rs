@@ -18,102 +18,117 @@
 //! let base_img = captcha.to_base64();
 //! println!("base_img: {}", base_img);
 //! ```
+pub use ab_glyph::FontArc;
 use image::DynamicImage;
 use imageproc::noise::{gaussian_noise_mut, salt_and_pepper_noise_mut};
-use rand::{Rng, rng};
+use rand::Rng;
 
 use crate::captcha::{
-    cyclic_write_character, draw_interference_ellipse, draw_interference_line, get_image,
-    to_base64_str,
+    StrokeStyle, cyclic_write_character, draw_interference_ellipse, draw_interference_line,
+    draw_interference_shapes, encode_image, get_image, to_base64_str,
 };
 
+#[cfg(feature = "actix")]
+pub mod actix;
+#[cfg(feature = "axum")]
+pub mod axum;
+#[cfg(feature = "capi")]
+mod capi;
 mod captcha;
-
+#[cfg(feature = "serde")]
+mod config;
+mod limit;
+mod policy;
+mod pool;
+#[cfg(any(feature = "stateless", feature = "compact"))]
+mod stateless;
+mod store;
+#[cfg(feature = "tower")]
+pub mod tower;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(any(
+    feature = "actix",
+    feature = "axum",
+    feature = "server",
+    feature = "tower"
+))]
+mod web;
+
+pub use captcha::{
+    Distortion, MAX_DISTORTIONS, OutputFormat, RandomSource, Sampling, set_random_source,
+};
+#[cfg(feature = "serde")]
+pub use config::CaptchaConfig;
+pub use limit::{MemoryRateLimiter, RateLimiter};
+pub use policy::{DEFAULT_CONFUSABLES, MAX_CONFUSABLES, MatchPolicy};
+pub use pool::{CaptchaPool, PoolMetrics};
+#[cfg(all(feature = "compact", feature = "stateless"))]
+pub use stateless::verify_compact_with;
+#[cfg(any(feature = "stateless", feature = "compact"))]
+pub use stateless::{
+    Clock, DEFAULT_LEEWAY, FixedClock, SystemClock, VerifyError, set_default_clock,
+};
+#[cfg(feature = "stateless")]
+pub use stateless::{
+    Key, KeyAlgorithm, KeySet, MAX_POW_DIFFICULTY, MemoryReplayGuard, ReplayGuard, TokenContext,
+    TokenOptions, Verifier, issue_pow, issue_pow_with, solve_pow, verify, verify_detailed,
+    verify_detailed_with_keys, verify_once, verify_once_with_options, verify_pow, verify_pow_once,
+    verify_pow_with, verify_with_context, verify_with_keys, verify_with_options,
+};
+#[cfg(feature = "encrypted")]
+pub use stateless::{
+    SealedChallenge, SealingKey, open_sealed, verify_sealed, verify_sealed_detailed,
+    verify_sealed_once, verify_sealed_with,
+};
+#[cfg(feature = "compact")]
+pub use stateless::{verify_compact, verify_compact_detailed};
+pub use store::{CaptchaStore, MemoryStore, StoredCaptcha};
+#[cfg(any(
+    feature = "actix",
+    feature = "axum",
+    feature = "server",
+    feature = "tower"
+))]
+pub use web::{CaptchaRejection, CaptchaService, Challenge, ChallengeBody, VerifyBody};
+
+/// Maximum number of fonts a builder keeps.
+pub const MAX_FONTS: usize = 16;
+
+/// Longest captcha text in characters, set with `text` or `length`.
+pub const MAX_LENGTH: usize = 32;
+
+/// A generated captcha. Built by [`CaptchaBuilder`], and non-exhaustive so new
+/// settings can be recorded without breaking callers.
+#[non_exhaustive]
 pub struct Captcha {
     pub text: String,
     pub image: DynamicImage,
     pub compression: u8,
     pub dark_mode: bool,
-}
-
-#[cfg(feature = "stateless")]
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
-struct Claims {
-    hash: String,
-    exp: usize,
+    pub format: OutputFormat,
+    pub match_policy: MatchPolicy,
 }
 
 impl Captcha {
     pub fn to_base64(&self) -> String {
-        to_base64_str(&self.image, self.compression)
+        to_base64_str(&self.image, self.compression, self.format)
     }
 
-    #[cfg(feature = "stateless")]
-    pub fn as_token(&self, secret: &str, expiration_seconds: u64) -> Option<String> {
-        use jsonwebtoken::{EncodingKey, Header, encode};
-        use sha2::{Digest, Sha256};
-        use std::time::{SystemTime, UNIX_EPOCH};
-
-        let exp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("Time went backwards")
-            .as_secs()
-            + expiration_seconds;
-
-        let mut hasher = Sha256::new();
-        hasher.update(secret.as_bytes());
-        hasher.update(self.text.to_lowercase().as_bytes());
-        let hash_result = hasher.finalize();
-        let hash = base64::Engine::encode(
-            &base64::engine::general_purpose::URL_SAFE_NO_PAD,
-            hash_result,
-        );
-
-        let claims = Claims {
-            hash,
-            exp: exp as usize,
-        };
-
-        encode(
-            &Header::default(),
-            &claims,
-            &EncodingKey::from_secret(secret.as_ref()),
-        )
-        .ok()
+    /// The encoded image, e.g. to write it to a file or an HTTP response.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        encode_image(&self.image, self.compression, self.format)
     }
 
-    #[cfg(feature = "stateless")]
-    pub fn as_tuple(&self, secret: &str, expiration_seconds: u64) -> Option<(String, String)> {
-        self.as_token(secret, expiration_seconds)
-            .map(|token| (self.to_base64(), token))
+    /// Compare a provided answer to the text using the captcha's `MatchPolicy`.
+    pub fn matches(&self, provided_solution: &str) -> bool {
+        self.match_policy.matches(&self.text, provided_solution)
     }
 }
 
-#[cfg(feature = "stateless")]
-pub fn verify(token: &str, provided_solution: &str, secret: &str) -> Option<bool> {
-    use jsonwebtoken::{DecodingKey, Validation, decode};
-    use sha2::{Digest, Sha256};
-
-    let token_data = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(secret.as_ref()),
-        &Validation::default(),
-    )
-    .ok()?;
-
-    let mut hasher = Sha256::new();
-    hasher.update(secret.as_bytes());
-    hasher.update(provided_solution.to_lowercase().as_bytes());
-    let expected_hash_result = hasher.finalize();
-    let expected_hash = base64::Engine::encode(
-        &base64::engine::general_purpose::URL_SAFE_NO_PAD,
-        expected_hash_result,
-    );
-
-    Some(token_data.claims.hash == expected_hash)
-}
-
-#[derive(Default)]
+/// Captcha settings. A configured builder can be cloned or shared between threads
+/// and produce any number of captchas with [`CaptchaBuilder::generate`].
+#[derive(Default, Clone)]
 pub struct CaptchaBuilder {
     text: Option<String>,
     length: usize,
@@ -127,6 +142,18 @@ pub struct CaptchaBuilder {
     interference_lines: usize,
     interference_ellipses: usize,
     distortion: u32,
+    sampling: Sampling,
+    distortions: Vec<Distortion>,
+    strike_through: bool,
+    glyph_arcs: usize,
+    interference_shapes: usize,
+    stroke_width: u32,
+    antialias: bool,
+    filled_ellipses: bool,
+    match_policy: MatchPolicy,
+    fonts: Vec<FontArc>,
+    format: OutputFormat,
+    seed: Option<u64>,
 }
 
 impl CaptchaBuilder {
@@ -144,16 +171,28 @@ impl CaptchaBuilder {
             interference_lines: 2,
             interference_ellipses: 2,
             distortion: 0,
+            sampling: Sampling::Nearest,
+            distortions: Vec::new(),
+            strike_through: false,
+            glyph_arcs: 0,
+            interference_shapes: 0,
+            stroke_width: 1,
+            antialias: false,
+            filled_ellipses: false,
+            match_policy: MatchPolicy::default(),
+            fonts: Vec::new(),
+            format: OutputFormat::Jpeg,
+            seed: None,
         }
     }
 
     pub fn text(mut self, text: String) -> Self {
-        self.text = Some(text.chars().take(32).collect());
+        self.text = Some(text.chars().take(MAX_LENGTH).collect());
         self
     }
 
     pub fn length(mut self, length: usize) -> Self {
-        self.length = length.clamp(1, 32);
+        self.length = length.clamp(1, MAX_LENGTH);
         self
     }
 
@@ -202,14 +241,104 @@ impl CaptchaBuilder {
         self
     }
 
+    /// Number of random distraction shapes (polygons, arcs and dot clusters).
+    pub fn interference_shapes(mut self, shapes: usize) -> Self {
+        self.interference_shapes = shapes.min(100);
+        self
+    }
+
+    /// Width in pixels of interference lines and outlines.
+    pub fn stroke_width(mut self, width: u32) -> Self {
+        self.stroke_width = width.clamp(1, 10);
+        self
+    }
+
+    /// Anti-alias interference and occlusion strokes.
+    pub fn antialias(mut self, antialias: bool) -> Self {
+        self.antialias = antialias;
+        self
+    }
+
+    /// Fill the distraction ellipses instead of drawing their outline.
+    pub fn filled_ellipses(mut self, filled: bool) -> Self {
+        self.filled_ellipses = filled;
+        self
+    }
+
     pub fn distortion(mut self, distortion: u32) -> Self {
         self.distortion = distortion.min(100);
         self
     }
 
+    /// Draw a variable-thickness curve through the text, crossing every character.
+    pub fn strike_through(mut self, strike_through: bool) -> Self {
+        self.strike_through = strike_through;
+        self
+    }
+
+    /// Number of arcs joining adjacent characters, drawn in the characters' own colors.
+    pub fn glyph_arcs(mut self, arcs: usize) -> Self {
+        self.glyph_arcs = arcs.min(100);
+        self
+    }
+
+    pub fn sampling(mut self, sampling: Sampling) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    /// Stack an additional distortion, applied in insertion order after the wavy
+    /// `distortion` level. At most [`MAX_DISTORTIONS`] are kept, and distortions
+    /// added beyond that are ignored.
+    pub fn add_distortion(mut self, distortion: Distortion) -> Self {
+        if self.distortions.len() < captcha::MAX_DISTORTIONS {
+            self.distortions.push(distortion);
+        }
+        self
+    }
+
+    /// Add a font. Each character is drawn with a random font among those added,
+    /// or the embedded font if none is. At most `MAX_FONTS` are kept.
+    pub fn font(mut self, font: FontArc) -> Self {
+        if self.fonts.len() < MAX_FONTS {
+            self.fonts.push(font);
+        }
+        self
+    }
+
+    pub fn format(mut self, format: OutputFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Draw from a generator seeded with `seed`, so every captcha generated by this
+    /// builder has the same text and image. Meant for tests and reproducible output,
+    /// never for captchas served to users.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// How answers are compared, also embedded into stateless and sealed tokens.
+    pub fn match_policy(mut self, policy: MatchPolicy) -> Self {
+        self.match_policy = policy;
+        self
+    }
+
     pub fn build(self) -> Captcha {
-        let text = match self.text {
-            Some(t) if !t.is_empty() => t,
+        self.generate()
+    }
+
+    /// Generate a new captcha without consuming the builder.
+    ///
+    /// Every call draws fresh random text, unless a fixed `text` was set.
+    pub fn generate(&self) -> Captcha {
+        captcha::with_seed(self.seed, || self.render())
+    }
+
+    fn render(&self) -> Captcha {
+        let text = match &self.text {
+            Some(t) if !t.is_empty() => t.clone(),
             _ => captcha::get_captcha(self.length, &self.characters).join(""),
         };
 
@@ -219,22 +348,52 @@ impl CaptchaBuilder {
         let res: Vec<String> = text.chars().map(|x| x.to_string()).collect();
 
         // Loop to write the verification code string into the background image
-        cyclic_write_character(&res, &mut image, self.dark_mode, self.drop_shadow);
+        let glyphs = cyclic_write_character(
+            &res,
+            &mut image,
+            self.dark_mode,
+            self.drop_shadow,
+            &self.fonts,
+        );
+
+        // Occlude the characters themselves before distorting them together
+        if self.strike_through {
+            captcha::draw_strike_through(&mut image, &glyphs, self.dark_mode, self.antialias);
+        }
+        captcha::draw_glyph_arcs(&mut image, &glyphs, self.glyph_arcs, self.antialias);
 
         if self.distortion > 0 {
-            captcha::apply_wavy_distortion(&mut image, self.distortion);
+            captcha::apply_wavy_distortion(&mut image, self.distortion, self.sampling);
+        }
+
+        for distortion in &self.distortions {
+            distortion.apply(&mut image, self.sampling);
         }
 
+        let style = StrokeStyle {
+            width: self.stroke_width as f32,
+            antialias: self.antialias,
+        };
+
         // Draw interference lines
         for _ in 0..self.interference_lines {
-            draw_interference_line(&mut image, self.dark_mode);
+            draw_interference_line(&mut image, self.dark_mode, style);
         }
 
         // Draw distraction circles
-        draw_interference_ellipse(self.interference_ellipses, &mut image, self.dark_mode);
+        draw_interference_ellipse(
+            self.interference_ellipses,
+            &mut image,
+            self.dark_mode,
+            style,
+            self.filled_ellipses,
+        );
+
+        // Draw distraction shapes
+        draw_interference_shapes(self.interference_shapes, &mut image, self.dark_mode, style);
 
         if self.complexity > 1 {
-            let mut rng = rng();
+            let mut rng = captcha::rng();
 
             gaussian_noise_mut(
                 &mut image,
@@ -255,13 +414,15 @@ impl CaptchaBuilder {
             image: DynamicImage::ImageRgb8(image),
             compression: self.compression,
             dark_mode: self.dark_mode,
+            format: self.format,
+            match_policy: self.match_policy.clone(),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::CaptchaBuilder;
+    use crate::{CaptchaBuilder, Distortion, OutputFormat, Sampling};
 
     #[test]
     fn it_generates_a_captcha() {
@@ -339,6 +500,111 @@ mod tests {
         assert!(base_img.starts_with("data:image/jpeg;base64,"));
     }
 
+    #[test]
+    fn it_generates_captcha_with_bilinear_distortion() {
+        let captcha = CaptchaBuilder::new()
+            .text(String::from("smooth"))
+            .width(200)
+            .height(70)
+            .distortion(20)
+            .sampling(Sampling::Bilinear)
+            .build();
+
+        assert_eq!(captcha.text, "smooth");
+        assert_eq!(captcha.image.width(), 200);
+        assert_eq!(captcha.image.height(), 70);
+    }
+
+    #[test]
+    fn it_generates_captcha_with_glyph_occlusion() {
+        let captcha = CaptchaBuilder::new()
+            .text(String::from("strike"))
+            .width(200)
+            .height(70)
+            .strike_through(true)
+            .glyph_arcs(3)
+            .distortion(3)
+            .build();
+
+        assert_eq!(captcha.text, "strike");
+        let base_img = captcha.to_base64();
+        assert!(base_img.starts_with("data:image/jpeg;base64,"));
+    }
+
+    #[test]
+    fn it_generates_captcha_with_stacked_distortions() {
+        let mut builder = CaptchaBuilder::new()
+            .text(String::from("warp"))
+            .width(200)
+            .height(70)
+            .add_distortion(Distortion::Swirl(40))
+            .add_distortion(Distortion::Ripple(30))
+            .add_distortion(Distortion::Fisheye(50))
+            .add_distortion(Distortion::Perspective(50))
+            .add_distortion(Distortion::Elastic(40));
+        for _ in 0..20 {
+            builder = builder.add_distortion(Distortion::Wavy(5));
+        }
+        assert_eq!(builder.distortions.len(), crate::captcha::MAX_DISTORTIONS);
+
+        let captcha = builder.build();
+        assert_eq!(captcha.text, "warp");
+        let base_img = captcha.to_base64();
+        assert!(base_img.starts_with("data:image/jpeg;base64,"));
+    }
+
+    #[test]
+    fn it_generates_concurrently_from_a_shared_builder() {
+        let builder = std::sync::Arc::new(CaptchaBuilder::new().length(6).complexity(3));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let builder = builder.clone();
+                std::thread::spawn(move || builder.generate())
+            })
+            .collect();
+
+        for handle in handles {
+            assert_eq!(handle.join().unwrap().text.len(), 6);
+        }
+
+        let copy = (*builder).clone().text(String::from("fixed"));
+        assert_eq!(copy.generate().text, "fixed");
+        assert_eq!(copy.generate().text, "fixed");
+        assert_eq!(builder.generate().text.len(), 6);
+    }
+
+    #[test]
+    fn it_repeats_seeded_captchas() {
+        let builder = CaptchaBuilder::new()
+            .complexity(4)
+            .distortion(10)
+            .add_distortion(Distortion::Elastic(30))
+            .interference_shapes(3)
+            .seed(42);
+
+        let first = builder.generate();
+        let second = builder.generate();
+        assert_eq!(first.text, second.text);
+        assert_eq!(first.image, second.image);
+
+        let other = builder.clone().seed(43).generate();
+        assert_ne!(first.image, other.image);
+    }
+
+    #[test]
+    fn it_encodes_png_and_custom_fonts() {
+        let font = crate::captcha::get_font();
+        let captcha = CaptchaBuilder::new()
+            .text(String::from("fonts"))
+            .font(font.clone())
+            .font(font)
+            .format(OutputFormat::Png)
+            .build();
+
+        assert!(captcha.to_base64().starts_with("data:image/png;base64,"));
+        assert!(captcha.to_bytes().starts_with(b"\x89PNG"));
+    }
+
     #[test]
     fn it_generates_captcha_with_custom_interference_and_shadow() {
         let captcha = CaptchaBuilder::new()
@@ -355,6 +621,24 @@ mod tests {
         assert!(base_img.starts_with("data:image/jpeg;base64,"));
     }
 
+    #[test]
+    fn it_generates_captcha_with_thick_antialiased_interference() {
+        let captcha = CaptchaBuilder::new()
+            .text(String::from("thick"))
+            .width(200)
+            .height(70)
+            .stroke_width(3)
+            .antialias(true)
+            .filled_ellipses(true)
+            .interference_lines(4)
+            .interference_shapes(6)
+            .build();
+
+        assert_eq!(captcha.text, "thick");
+        let base_img = captcha.to_base64();
+        assert!(base_img.starts_with("data:image/jpeg;base64,"));
+    }
+
     #[test]
     fn it_generates_captcha_with_custom_characters() {
         let captcha = CaptchaBuilder::new()
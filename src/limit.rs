@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Entries kept before idle buckets are pruned.
+const PRUNE_THRESHOLD: usize = 4096;
+
+/// Limits verification attempts per key, e.g. a challenge id or a client address.
+pub trait RateLimiter: Send + Sync {
+    /// Take one attempt for `key` at `now` (seconds since the Unix epoch).
+    ///
+    /// Returns the number of seconds to wait if the key is out of attempts or
+    /// locked out after failures.
+    fn acquire(&self, key: &str, now: u64) -> Result<(), u64>;
+
+    /// Record a wrong answer for `key`, extending its backoff.
+    fn record_failure(&self, key: &str, now: u64);
+
+    /// Record a correct answer for `key`, resetting its backoff.
+    fn record_success(&self, key: &str);
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: u32,
+    refilled_at: u64,
+    failures: u32,
+    blocked_until: u64,
+}
+
+/// An in-memory token-bucket [`RateLimiter`] with exponential backoff.
+///
+/// Every key starts with `capacity` attempts and regains one every
+/// `refill_interval` seconds. Each consecutive failure blocks the key for twice as
+/// long as the previous one, starting at `base_backoff` and capped at `max_backoff`,
+/// which acts as the lockout duration. Failures are forgotten once a key has not
+/// been blocked for `max_backoff` seconds, so keys that are never solved, like
+/// abandoned challenges, are eventually pruned.
+#[derive(Debug)]
+pub struct MemoryRateLimiter {
+    capacity: u32,
+    refill_interval: u64,
+    base_backoff: u64,
+    max_backoff: u64,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl Default for MemoryRateLimiter {
+    fn default() -> Self {
+        MemoryRateLimiter {
+            capacity: 5,
+            refill_interval: 60,
+            base_backoff: 1,
+            max_backoff: 300,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl MemoryRateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attempts available to a fresh key, between 1 and 1000.
+    pub fn capacity(mut self, capacity: u32) -> Self {
+        self.capacity = capacity.clamp(1, 1000);
+        self
+    }
+
+    /// Seconds to regain one attempt, at least 1.
+    pub fn refill_interval(mut self, seconds: u64) -> Self {
+        self.refill_interval = seconds.max(1);
+        self
+    }
+
+    /// Backoff after the first failure and the lockout ceiling, in seconds.
+    pub fn backoff(mut self, base: u64, max: u64) -> Self {
+        self.base_backoff = base;
+        self.max_backoff = max.max(base);
+        self
+    }
+
+    /// Number of keys currently tracked.
+    pub fn len(&self) -> usize {
+        self.buckets.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn refill(&self, bucket: &mut Bucket, now: u64) {
+        let elapsed = now.saturating_sub(bucket.refilled_at);
+        let regained = elapsed / self.refill_interval;
+        if regained > 0 {
+            let tokens = (bucket.tokens as u64 + regained).min(self.capacity as u64);
+            bucket.tokens = tokens as u32;
+            bucket.refilled_at += regained * self.refill_interval;
+        }
+    }
+
+    /// Forget the failures of a key that has not been blocked for `max_backoff`.
+    fn decay(&self, bucket: &mut Bucket, now: u64) {
+        if bucket.blocked_until.saturating_add(self.max_backoff) <= now {
+            bucket.failures = 0;
+        }
+    }
+
+    /// Whether a bucket is back to its initial state and can be forgotten.
+    fn is_idle(&self, bucket: &Bucket, now: u64) -> bool {
+        let full_at =
+            bucket.refilled_at + (self.capacity - bucket.tokens) as u64 * self.refill_interval;
+        bucket.blocked_until.saturating_add(self.max_backoff) <= now && full_at <= now
+    }
+}
+
+impl RateLimiter for MemoryRateLimiter {
+    fn acquire(&self, key: &str, now: u64) -> Result<(), u64> {
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= PRUNE_THRESHOLD {
+            buckets.retain(|_, bucket| !self.is_idle(bucket, now));
+        }
+
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: self.capacity,
+            refilled_at: now,
+            failures: 0,
+            blocked_until: 0,
+        });
+        self.refill(bucket, now);
+        self.decay(bucket, now);
+
+        if bucket.blocked_until > now {
+            return Err(bucket.blocked_until - now);
+        }
+        if bucket.tokens == 0 {
+            return Err((bucket.refilled_at + self.refill_interval).saturating_sub(now));
+        }
+        bucket.tokens -= 1;
+        Ok(())
+    }
+
+    fn record_failure(&self, key: &str, now: u64) {
+        let mut buckets = self.buckets.lock().unwrap();
+        if let Some(bucket) = buckets.get_mut(key) {
+            self.decay(bucket, now);
+            bucket.failures = bucket.failures.saturating_add(1);
+            let doublings = (bucket.failures - 1).min(63);
+            let backoff = self
+                .base_backoff
+                .checked_shl(doublings)
+                .unwrap_or(u64::MAX)
+                .min(self.max_backoff);
+            bucket.blocked_until = now.saturating_add(backoff);
+        }
+    }
+
+    fn record_success(&self, key: &str) {
+        let mut buckets = self.buckets.lock().unwrap();
+        if let Some(bucket) = buckets.get_mut(key) {
+            bucket.failures = 0;
+            bucket.blocked_until = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_refills_attempts_over_time() {
+        let limiter = MemoryRateLimiter::new().capacity(2).refill_interval(10);
+        assert_eq!(limiter.acquire("a", 100), Ok(()));
+        assert_eq!(limiter.acquire("a", 100), Ok(()));
+        assert_eq!(limiter.acquire("a", 104), Err(6));
+        assert_eq!(limiter.acquire("b", 104), Ok(()));
+        assert_eq!(limiter.acquire("a", 110), Ok(()));
+        assert_eq!(limiter.acquire("a", 110), Err(10));
+    }
+
+    #[test]
+    fn it_backs_off_exponentially_up_to_the_lockout() {
+        let limiter = MemoryRateLimiter::new().capacity(100).backoff(2, 10);
+        let mut now = 1000;
+        for expected in [2, 4, 8, 10, 10] {
+            assert_eq!(limiter.acquire("a", now), Ok(()));
+            limiter.record_failure("a", now);
+            assert_eq!(limiter.acquire("a", now), Err(expected));
+            now += expected;
+        }
+
+        limiter.record_success("a");
+        assert_eq!(limiter.acquire("a", now), Ok(()));
+        limiter.record_failure("a", now);
+        assert_eq!(limiter.acquire("a", now), Err(2));
+    }
+
+    #[test]
+    fn it_forgets_failures_after_a_quiet_period() {
+        let limiter = MemoryRateLimiter::new().capacity(100).backoff(2, 10);
+        assert_eq!(limiter.acquire("a", 1000), Ok(()));
+        limiter.record_failure("a", 1000);
+        assert_eq!(limiter.acquire("a", 1002), Ok(()));
+        limiter.record_failure("a", 1002);
+        assert_eq!(limiter.acquire("a", 1002), Err(4));
+
+        // Blocked until 1006, failures are forgotten 10 seconds later
+        assert_eq!(limiter.acquire("a", 1016), Ok(()));
+        limiter.record_failure("a", 1016);
+        assert_eq!(limiter.acquire("a", 1016), Err(2));
+    }
+
+    #[test]
+    fn it_prunes_keys_that_failed_and_were_abandoned() {
+        let limiter = MemoryRateLimiter::new()
+            .capacity(1)
+            .refill_interval(1)
+            .backoff(1, 10);
+        for i in 0..PRUNE_THRESHOLD {
+            let key = format!("challenge:{}", i);
+            assert_eq!(limiter.acquire(&key, 1000), Ok(()));
+            limiter.record_failure(&key, 1000);
+        }
+        assert_eq!(limiter.len(), PRUNE_THRESHOLD);
+
+        // Still locked out, nothing can be pruned yet
+        assert_eq!(limiter.acquire("fresh", 1005), Ok(()));
+        assert_eq!(limiter.len(), PRUNE_THRESHOLD + 1);
+
+        // Ten seconds after the lockouts ended, only the new key is left
+        assert_eq!(limiter.acquire("later", 1011), Ok(()));
+        assert_eq!(limiter.len(), 1);
+    }
+}
@@ -0,0 +1,431 @@
+#[cfg(feature = "nfkc")]
+use unicode_normalization::UnicodeNormalization;
+
+/// Characters commonly misread in captcha images, mapped to a shared representative.
+pub const DEFAULT_CONFUSABLES: [(char, char); 4] = [('0', 'O'), ('1', 'l'), ('I', 'l'), ('|', 'l')];
+
+/// Maximum number of confusable mappings kept by a policy.
+pub const MAX_CONFUSABLES: usize = 64;
+
+/// How a provided answer is compared to the captcha text.
+///
+/// Both sides go through the same normalization: Unicode NFKC (with the `nfkc`
+/// feature), whitespace trimming, case folding and then the confusable mapping.
+/// The default policy only folds case, as captcha-rs always did.
+///
+/// A tolerated typo may drop any character, but only inserts or substitutes the
+/// default captcha characters, digits and ASCII letters, whether the answer is
+/// compared in plain text or against a hash.
+///
+/// ```rust
+/// use captcha_rs::MatchPolicy;
+///
+/// let policy = MatchPolicy::new().trim(true).confusables(true).edit_distance(1);
+///
+/// assert!(policy.matches("O1Ab5", " o1ab5 "));
+/// assert!(policy.matches("O1Ab5", "0lab5"));
+/// assert!(policy.matches("O1Ab5", "O1Ab"));
+/// assert!(!policy.matches("O1Ab5", "O1A"));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "PolicyFields"))]
+pub struct MatchPolicy {
+    case_sensitive: bool,
+    trim: bool,
+    nfkc: bool,
+    confusables: Vec<(char, char)>,
+    max_edits: u8,
+}
+
+impl MatchPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn case_sensitive(mut self, case_sensitive: bool) -> Self {
+        self.case_sensitive = case_sensitive;
+        self
+    }
+
+    /// Ignore leading and trailing whitespace.
+    pub fn trim(mut self, trim: bool) -> Self {
+        self.trim = trim;
+        self
+    }
+
+    /// Apply Unicode NFKC normalization, e.g. to accept full-width characters.
+    #[cfg(feature = "nfkc")]
+    pub fn nfkc(mut self, nfkc: bool) -> Self {
+        self.nfkc = nfkc;
+        self
+    }
+
+    /// Use the `DEFAULT_CONFUSABLES` table, or clear every mapping.
+    pub fn confusables(mut self, enabled: bool) -> Self {
+        self.confusables = if enabled {
+            DEFAULT_CONFUSABLES.to_vec()
+        } else {
+            Vec::new()
+        };
+        self
+    }
+
+    /// Treat `from` as `to`. At most `MAX_CONFUSABLES` mappings are kept.
+    pub fn confusable(mut self, from: char, to: char) -> Self {
+        if self.confusables.len() < MAX_CONFUSABLES {
+            self.confusables.push((from, to));
+        }
+        self
+    }
+
+    /// Number of typos (insertion, deletion or substitution) tolerated, 0 or 1.
+    pub fn edit_distance(mut self, edits: u8) -> Self {
+        self.max_edits = edits.min(1);
+        self
+    }
+
+    pub fn max_edits(&self) -> u8 {
+        self.max_edits
+    }
+
+    /// Normalize an answer according to this policy.
+    pub fn normalize(&self, answer: &str) -> String {
+        #[cfg(feature = "nfkc")]
+        let mut answer: String = if self.nfkc {
+            answer.nfkc().collect()
+        } else {
+            answer.to_string()
+        };
+        #[cfg(not(feature = "nfkc"))]
+        let mut answer = answer.to_string();
+        if self.trim {
+            answer = answer.trim().to_string();
+        }
+        let answer = self.fold_case(&answer);
+        answer.chars().map(|c| self.map_confusable(c)).collect()
+    }
+
+    /// Whether `provided` matches the `expected` captcha text.
+    pub fn matches(&self, expected: &str, provided: &str) -> bool {
+        let expected: Vec<char> = self.normalize(expected).chars().collect();
+        let provided: Vec<char> = self.normalize(provided).chars().collect();
+        if self.max_edits == 0 {
+            expected == provided
+        } else {
+            let symbols = self.edit_symbols(&edit_alphabet());
+            within_one_edit(&expected, &provided, |c| symbols.binary_search(&c).is_ok())
+        }
+    }
+
+    /// Normalized answers within the tolerated edit distance of `provided`, using
+    /// the characters of `alphabet` for insertions and substitutions.
+    ///
+    /// Used to match hashed answers, which can't be compared by distance. Answers
+    /// longer than any captcha text could match have no candidates, so the work
+    /// is bounded whatever a client sends.
+    #[cfg(any(feature = "stateless", feature = "compact"))]
+    pub(crate) fn candidates(&self, provided: &str, alphabet: &[char]) -> Vec<String> {
+        // Leave room for the tolerated insertions and surrounding whitespace
+        let limit = crate::MAX_LENGTH + self.max_edits as usize;
+        if provided.trim().chars().nth(limit).is_some() {
+            return Vec::new();
+        }
+
+        let provided: Vec<char> = self.normalize(provided).chars().collect();
+        // NFKC may expand a character into several
+        if provided.len() > limit {
+            return Vec::new();
+        }
+        let mut result = vec![provided.iter().collect::<String>()];
+        if self.max_edits == 0 {
+            return result;
+        }
+
+        let symbols = self.edit_symbols(alphabet);
+        for i in 0..=provided.len() {
+            let (head, tail) = provided.split_at(i);
+            for &symbol in &symbols {
+                // Insertion before position `i`
+                result.push(head.iter().chain([&symbol]).chain(tail).collect());
+                // Substitution of the character at `i`
+                if let Some((&current, rest)) = tail.split_first()
+                    && current != symbol
+                {
+                    result.push(head.iter().chain([&symbol]).chain(rest).collect());
+                }
+            }
+            // Deletion of the character at `i`
+            if let Some((_, rest)) = tail.split_first() {
+                result.push(head.iter().chain(rest).collect());
+            }
+        }
+        result.sort_unstable();
+        result.dedup();
+        result
+    }
+
+    /// The characters of `alphabet` after normalization, sorted and deduplicated.
+    fn edit_symbols(&self, alphabet: &[char]) -> Vec<char> {
+        let mut symbols: Vec<char> = alphabet
+            .iter()
+            .flat_map(|c| self.normalize(&c.to_string()).chars().collect::<Vec<_>>())
+            .collect();
+        symbols.sort_unstable();
+        symbols.dedup();
+        symbols
+    }
+
+    fn fold_case(&self, value: &str) -> String {
+        if self.case_sensitive {
+            value.to_string()
+        } else {
+            value.to_lowercase()
+        }
+    }
+
+    fn map_confusable(&self, c: char) -> char {
+        for &(from, to) in &self.confusables {
+            // Fold the table as well, so case-insensitive policies stay consistent
+            if self.fold_case(&from.to_string()) == c.to_string() {
+                return self.fold_case(&to.to_string()).chars().next().unwrap_or(to);
+            }
+        }
+        c
+    }
+
+    /// Compact binary form embedded into tokens, so verification uses the policy
+    /// the token was issued with.
+    #[cfg(any(feature = "stateless", feature = "compact"))]
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let flags = self.case_sensitive as u8 | (self.trim as u8) << 1 | (self.nfkc as u8) << 2;
+        let mut bytes = vec![flags, self.max_edits];
+        for &(from, to) in &self.confusables {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(from.encode_utf8(&mut buf).as_bytes());
+            bytes.extend_from_slice(to.encode_utf8(&mut buf).as_bytes());
+        }
+        bytes
+    }
+
+    #[cfg(any(feature = "stateless", feature = "compact"))]
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let (&flags, rest) = bytes.split_first()?;
+        let (&max_edits, rest) = rest.split_first()?;
+        if flags > 0b111 || max_edits > 1 {
+            return None;
+        }
+        // A policy normalizing with NFKC can't be honored without the feature
+        if flags & 4 != 0 && !cfg!(feature = "nfkc") {
+            return None;
+        }
+        let chars: Vec<char> = std::str::from_utf8(rest).ok()?.chars().collect();
+        if !chars.len().is_multiple_of(2) || chars.len() / 2 > MAX_CONFUSABLES {
+            return None;
+        }
+        Some(MatchPolicy {
+            case_sensitive: flags & 1 != 0,
+            trim: flags & 2 != 0,
+            nfkc: flags & 4 != 0,
+            confusables: chars
+                .chunks_exact(2)
+                .map(|pair| (pair[0], pair[1]))
+                .collect(),
+            max_edits,
+        })
+    }
+}
+
+/// Fields of a deserialized [`MatchPolicy`], applied through its setters so a
+/// loaded policy is clamped like one built in code.
+#[cfg(feature = "serde")]
+#[derive(Default, serde::Deserialize)]
+#[serde(default)]
+struct PolicyFields {
+    case_sensitive: bool,
+    trim: bool,
+    nfkc: bool,
+    confusables: Vec<(char, char)>,
+    max_edits: u8,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<PolicyFields> for MatchPolicy {
+    type Error = &'static str;
+
+    fn try_from(fields: PolicyFields) -> Result<Self, Self::Error> {
+        let mut policy = MatchPolicy::new()
+            .case_sensitive(fields.case_sensitive)
+            .trim(fields.trim)
+            .edit_distance(fields.max_edits);
+        if fields.nfkc {
+            #[cfg(not(feature = "nfkc"))]
+            return Err("NFKC normalization requires the `nfkc` feature");
+            #[cfg(feature = "nfkc")]
+            {
+                policy = policy.nfkc(true);
+            }
+        }
+        for (from, to) in fields.confusables {
+            policy = policy.confusable(from, to);
+        }
+        Ok(policy)
+    }
+}
+
+/// Characters tried as insertions and substitutions when a policy tolerates a
+/// typo: the default captcha characters, digits and ASCII letters.
+pub(crate) fn edit_alphabet() -> Vec<char> {
+    crate::captcha::BASIC_CHAR
+        .into_iter()
+        .chain('0'..='9')
+        .chain('a'..='z')
+        .chain('A'..='Z')
+        .collect()
+}
+
+/// Whether `provided` turns into `expected` with at most one deletion, or one
+/// insertion or substitution of a character accepted by `allowed`.
+fn within_one_edit(expected: &[char], provided: &[char], allowed: impl Fn(char) -> bool) -> bool {
+    let prefix = expected
+        .iter()
+        .zip(provided)
+        .take_while(|(x, y)| x == y)
+        .count();
+    if expected.len() == provided.len() {
+        // Identical, or one character of `expected` substituted at `prefix`
+        prefix == expected.len()
+            || (allowed(expected[prefix]) && expected[prefix + 1..] == provided[prefix + 1..])
+    } else if expected.len() == provided.len() + 1 {
+        allowed(expected[prefix]) && expected[prefix + 1..] == provided[prefix..]
+    } else if provided.len() == expected.len() + 1 {
+        expected[prefix..] == provided[prefix + 1..]
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_policy_only_folds_case() {
+        let policy = MatchPolicy::default();
+        assert!(policy.matches("AbCd", "abcd"));
+        assert!(!policy.matches("AbCd", " abcd"));
+        assert!(!policy.matches("O1", "0l"));
+        assert_eq!(policy.normalize("AbCd"), "AbCd".to_lowercase());
+    }
+
+    #[cfg(feature = "nfkc")]
+    #[test]
+    fn it_normalizes_case_whitespace_and_width() {
+        let policy = MatchPolicy::new()
+            .case_sensitive(true)
+            .trim(true)
+            .nfkc(true);
+        assert!(policy.matches("AbC", " ＡｂＣ\n"));
+        assert!(!policy.matches("AbC", "abc"));
+    }
+
+    #[test]
+    fn it_maps_confusables_consistently() {
+        let policy = MatchPolicy::new().confusables(true);
+        assert!(policy.matches("O1I", "0lL"));
+        assert!(policy.matches("Oil", "oii"));
+
+        let sensitive = MatchPolicy::new().case_sensitive(true).confusables(true);
+        assert!(sensitive.matches("O1", "0l"));
+        assert!(!sensitive.matches("O1", "ol"));
+    }
+
+    #[test]
+    fn it_tolerates_one_edit() {
+        let policy = MatchPolicy::new().edit_distance(5);
+        assert_eq!(policy.max_edits(), 1);
+        assert!(policy.matches("abcde", "abxde"));
+        assert!(policy.matches("abcde", "abde"));
+        assert!(policy.matches("abcde", "abcdef"));
+        assert!(!policy.matches("abcde", "axcxe"));
+        assert!(!policy.matches("abcde", "abc"));
+    }
+
+    #[test]
+    fn it_only_inserts_and_substitutes_the_edit_alphabet() {
+        let policy = MatchPolicy::new().case_sensitive(true).edit_distance(1);
+        // Any character may be dropped or replaced by one of the alphabet
+        assert!(policy.matches("abcé", "abcéx"));
+        assert!(policy.matches("abcé", "abcéé"));
+        assert!(policy.matches("abe", "abé"));
+        // But only characters of the alphabet are inserted or substituted
+        assert!(!policy.matches("abé", "ab"));
+        assert!(!policy.matches("abé", "abe"));
+        assert!(policy.matches("abe", "ab"));
+    }
+
+    #[cfg(any(feature = "stateless", feature = "compact"))]
+    #[test]
+    fn it_matches_plain_text_like_the_candidates() {
+        let policy = MatchPolicy::new().confusables(true).edit_distance(1);
+        let alphabet = edit_alphabet();
+        for (expected, provided) in [
+            ("abcde", "abxde"),
+            ("abcde", "abde"),
+            ("abcde", "abcdef"),
+            ("abcdé", "abcd"),
+            ("abcdé", "abcde"),
+            ("ab-de", "abde"),
+            ("O1Ab5", "0lab"),
+        ] {
+            let candidates = policy.candidates(provided, &alphabet);
+            assert_eq!(
+                policy.matches(expected, provided),
+                candidates.contains(&policy.normalize(expected)),
+                "{expected} / {provided}"
+            );
+        }
+    }
+
+    #[cfg(any(feature = "stateless", feature = "compact"))]
+    #[test]
+    fn it_lists_candidates_within_one_edit() {
+        let policy = MatchPolicy::new().edit_distance(1);
+        let candidates = policy.candidates("abde", &['c']);
+        assert!(candidates.contains(&String::from("abcde")));
+        assert!(candidates.contains(&String::from("abd")));
+        // Only the alphabet is inserted, not the characters of the answer
+        assert!(!candidates.contains(&String::from("abdde")));
+    }
+
+    #[cfg(any(feature = "stateless", feature = "compact"))]
+    #[test]
+    fn it_has_no_candidates_for_overlong_answers() {
+        let policy = MatchPolicy::new().edit_distance(1);
+        assert_eq!(policy.candidates(&"a".repeat(33), &['a']).len(), 3);
+        assert!(policy.candidates(&"a".repeat(34), &['a']).is_empty());
+        assert!(policy.candidates(&"a".repeat(1 << 20), &['a']).is_empty());
+        assert!(
+            MatchPolicy::new()
+                .candidates(&"a".repeat(33), &[])
+                .is_empty()
+        );
+    }
+
+    #[cfg(any(feature = "stateless", feature = "compact"))]
+    #[test]
+    fn it_round_trips_through_bytes() {
+        let policy = MatchPolicy::new()
+            .case_sensitive(true)
+            .confusables(true)
+            .confusable('5', 'S')
+            .edit_distance(1);
+        #[cfg(feature = "nfkc")]
+        let policy = policy.nfkc(true);
+        assert_eq!(MatchPolicy::from_bytes(&policy.to_bytes()), Some(policy));
+        #[cfg(not(feature = "nfkc"))]
+        assert_eq!(MatchPolicy::from_bytes(&[4, 0]), None);
+        assert_eq!(MatchPolicy::from_bytes(&[8, 0]), None);
+        assert_eq!(MatchPolicy::from_bytes(&[0, 0, b'a']), None);
+    }
+}
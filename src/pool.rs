@@ -0,0 +1,345 @@
+use std::collections::VecDeque;
+use std::io;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::{Captcha, CaptchaBuilder};
+
+/// Counters describing how a [`CaptchaPool`] has been used so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolMetrics {
+    /// Captchas handed out from the pre-generated stock.
+    pub hits: u64,
+    /// Requests that found the pool empty.
+    pub misses: u64,
+    /// Captchas generated by the pool, both in the background and on a miss.
+    pub generated: u64,
+    /// Captchas currently waiting in the pool.
+    pub available: usize,
+}
+
+/// Pause before retrying after the factory panicked on the refill thread.
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+type Factory = dyn Fn() -> CaptchaBuilder + Send + Sync;
+
+struct Inner {
+    queue: Mutex<VecDeque<Captcha>>,
+    refill: Condvar,
+    factory: Box<Factory>,
+    capacity: usize,
+    low_water: usize,
+    shutdown: AtomicBool,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    generated: AtomicU64,
+}
+
+impl Inner {
+    /// Lock the queue, recovering it if a thread panicked while holding the lock.
+    ///
+    /// The queue only ever holds finished captchas, so it is consistent either way.
+    fn queue(&self) -> MutexGuard<'_, VecDeque<Captcha>> {
+        self.queue.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn generate(&self) -> Captcha {
+        self.generated.fetch_add(1, Ordering::Relaxed);
+        (self.factory)().build()
+    }
+
+    /// Generate captchas until the pool holds `capacity` of them.
+    fn fill(&self) {
+        while !self.shutdown.load(Ordering::Acquire) {
+            if self.queue().len() >= self.capacity {
+                return;
+            }
+            // Render outside the lock so consumers are never blocked on generation.
+            let captcha = self.generate();
+            let mut queue = self.queue();
+            if queue.len() >= self.capacity {
+                return;
+            }
+            queue.push_back(captcha);
+        }
+    }
+
+    fn refill_loop(&self) {
+        let mut failed = false;
+        loop {
+            {
+                let mut queue = self.queue();
+                // Pause after a failed refill instead of spinning on a factory that
+                // keeps panicking.
+                if failed && !self.shutdown.load(Ordering::Acquire) {
+                    queue = self
+                        .refill
+                        .wait_timeout(queue, RETRY_DELAY)
+                        .map_or_else(|error| error.into_inner().0, |(queue, _)| queue);
+                }
+                while !self.shutdown.load(Ordering::Acquire) && queue.len() >= self.low_water {
+                    queue = self
+                        .refill
+                        .wait(queue)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+            }
+            if self.shutdown.load(Ordering::Acquire) {
+                return;
+            }
+            // A panicking factory must not take the refill thread down with it.
+            failed = catch_unwind(AssertUnwindSafe(|| self.fill())).is_err();
+        }
+    }
+}
+
+/// A pool of pre-generated captchas for a single builder configuration.
+///
+/// Heavy configurations (high complexity, distortion) take noticeably longer to
+/// render than a request should wait. The pool keeps up to `capacity` captchas
+/// ready, and a background thread refills it whenever the stock drops below the
+/// low-water mark. Each captcha is handed out exactly once. If the factory panics
+/// on the refill thread, the refill is retried after a pause.
+///
+/// ```rust
+/// use captcha_rs::{CaptchaBuilder, CaptchaPool};
+///
+/// let pool = CaptchaPool::new(16, 4, || {
+///     CaptchaBuilder::new().length(5).complexity(10).distortion(20)
+/// })
+/// .expect("Failed to start the refill thread");
+///
+/// let captcha = pool.take();
+/// println!("text: {}", captcha.text);
+/// println!("metrics: {:?}", pool.metrics());
+/// ```
+pub struct CaptchaPool {
+    inner: Arc<Inner>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl CaptchaPool {
+    /// Create a pool holding up to `capacity` captchas, refilled in the background
+    /// once fewer than `low_water` remain.
+    ///
+    /// `factory` returns the builder used for every captcha in the pool.
+    /// `capacity` is at least 1 and `low_water` is clamped to `1..=capacity`.
+    ///
+    /// Fails if the background refill thread can't be spawned.
+    pub fn new<F>(capacity: usize, low_water: usize, factory: F) -> io::Result<Self>
+    where
+        F: Fn() -> CaptchaBuilder + Send + Sync + 'static,
+    {
+        let capacity = capacity.max(1);
+        let inner = Arc::new(Inner {
+            queue: Mutex::new(VecDeque::with_capacity(capacity)),
+            refill: Condvar::new(),
+            factory: Box::new(factory),
+            capacity,
+            low_water: low_water.clamp(1, capacity),
+            shutdown: AtomicBool::new(false),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            generated: AtomicU64::new(0),
+        });
+
+        let worker_inner = Arc::clone(&inner);
+        let worker = thread::Builder::new()
+            .name("captcha-pool".into())
+            .spawn(move || worker_inner.refill_loop())?;
+
+        Ok(CaptchaPool {
+            inner,
+            worker: Some(worker),
+        })
+    }
+
+    /// Take a captcha from the pool, generating one on the calling thread if the
+    /// pool is empty.
+    pub fn take(&self) -> Captcha {
+        match self.try_take() {
+            Some(captcha) => captcha,
+            None => self.inner.generate(),
+        }
+    }
+
+    /// Take a captcha from the pool, or `None` if the pool is currently empty.
+    pub fn try_take(&self) -> Option<Captcha> {
+        let mut queue = self.inner.queue();
+        let captcha = queue.pop_front();
+        if queue.len() < self.inner.low_water {
+            self.inner.refill.notify_one();
+        }
+        drop(queue);
+
+        match captcha {
+            Some(_) => self.inner.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.inner.misses.fetch_add(1, Ordering::Relaxed),
+        };
+        captcha
+    }
+
+    /// Fill the pool up to its capacity on the calling thread.
+    ///
+    /// Useful to warm the pool at startup before serving requests.
+    pub fn fill(&self) {
+        self.inner.fill();
+    }
+
+    /// Number of captchas currently waiting in the pool.
+    pub fn len(&self) -> usize {
+        self.inner.queue().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity
+    }
+
+    pub fn low_water(&self) -> usize {
+        self.inner.low_water
+    }
+
+    pub fn metrics(&self) -> PoolMetrics {
+        PoolMetrics {
+            hits: self.inner.hits.load(Ordering::Relaxed),
+            misses: self.inner.misses.load(Ordering::Relaxed),
+            generated: self.inner.generated.load(Ordering::Relaxed),
+            available: self.len(),
+        }
+    }
+}
+
+impl Drop for CaptchaPool {
+    fn drop(&mut self) {
+        self.inner.shutdown.store(true, Ordering::Release);
+        {
+            // Notify while holding the lock so the worker can't miss the wake-up.
+            let _queue = self.inner.queue();
+            self.inner.refill.notify_all();
+        }
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CaptchaPool;
+    use crate::CaptchaBuilder;
+    use std::collections::HashSet;
+    use std::panic::{AssertUnwindSafe, catch_unwind};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    /// Poll `condition` for up to 10 seconds.
+    fn wait_for(condition: impl Fn() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !condition() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    fn small_pool(capacity: usize, low_water: usize) -> CaptchaPool {
+        CaptchaPool::new(capacity, low_water, || {
+            CaptchaBuilder::new().length(8).width(130).height(40)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn it_fills_and_hands_out_captchas() {
+        let pool = small_pool(4, 2);
+        pool.fill();
+        assert_eq!(pool.len(), 4);
+
+        let captcha = pool.take();
+        assert_eq!(captcha.text.len(), 8);
+
+        let metrics = pool.metrics();
+        assert_eq!(metrics.hits, 1);
+        assert_eq!(metrics.misses, 0);
+        assert!(metrics.generated >= 4);
+    }
+
+    #[test]
+    fn it_falls_back_to_inline_generation_when_empty() {
+        let pool = small_pool(1, 1);
+        // Drain whatever the background thread has produced so far.
+        while pool.try_take().is_some() {}
+
+        let before = pool.metrics().misses;
+        let _ = pool.take();
+        assert!(pool.metrics().misses > before);
+    }
+
+    #[test]
+    fn it_hands_out_each_captcha_once() {
+        let pool = small_pool(8, 4);
+        pool.fill();
+
+        let mut seen = HashSet::new();
+        for _ in 0..32 {
+            let captcha = pool.take();
+            let bytes = captcha.image.as_bytes().to_vec();
+            assert!(seen.insert((captcha.text, bytes)));
+        }
+    }
+
+    #[test]
+    fn it_refills_in_the_background() {
+        let pool = small_pool(4, 4);
+        pool.fill();
+        for _ in 0..4 {
+            let _ = pool.take();
+        }
+
+        wait_for(|| pool.len() == pool.capacity());
+        assert_eq!(pool.len(), 4);
+    }
+
+    #[test]
+    fn it_keeps_serving_after_a_factory_panic() {
+        let calls = AtomicUsize::new(0);
+        let pool = CaptchaPool::new(2, 1, move || {
+            if calls.fetch_add(1, Ordering::Relaxed) == 0 {
+                panic!("first builder fails");
+            }
+            CaptchaBuilder::new().length(8).width(130).height(40)
+        })
+        .unwrap();
+
+        // Whichever thread hit the panic, the pool is still usable
+        let _ = catch_unwind(AssertUnwindSafe(|| pool.fill()));
+        assert_eq!(pool.take().text.len(), 8);
+    }
+
+    #[test]
+    fn it_keeps_refilling_after_a_factory_panic_in_the_background() {
+        let background_calls = Arc::new(AtomicUsize::new(0));
+        let calls = Arc::clone(&background_calls);
+        let pool = CaptchaPool::new(2, 2, move || {
+            let background = thread::current().name() == Some("captcha-pool");
+            if background && calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                panic!("first background builder fails");
+            }
+            CaptchaBuilder::new().length(8).width(130).height(40)
+        })
+        .unwrap();
+        wait_for(|| background_calls.load(Ordering::SeqCst) > 0);
+
+        // The refill thread survived and fills the pool after a pause
+        wait_for(|| pool.len() == 2);
+        assert_eq!(pool.len(), 2);
+        assert!(background_calls.load(Ordering::SeqCst) >= 3);
+    }
+}
@@ -0,0 +1,92 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Seconds of clock skew tolerated by default when checking expiry.
+pub const DEFAULT_LEEWAY: u64 = 60;
+
+/// Source of the current time used to issue and verify tokens.
+pub trait Clock: Send + Sync {
+    /// Seconds since the Unix epoch, or `None` if the time can't be read.
+    fn now(&self) -> Option<u64>;
+}
+
+static DEFAULT_CLOCK: OnceLock<Arc<dyn Clock>> = OnceLock::new();
+
+/// Replace [`SystemClock`] as the clock of `as_token`, `verify`, compact, sealed and
+/// proof-of-work tokens, replay guards and `TokenOptions::new`.
+///
+/// Can only be set once, before the first token is issued. Returns `false` if a
+/// default clock was already set.
+pub fn set_default_clock(clock: Arc<dyn Clock>) -> bool {
+    DEFAULT_CLOCK.set(clock).is_ok()
+}
+
+#[cfg(feature = "stateless")]
+pub(crate) fn default_clock() -> Arc<dyn Clock> {
+    match DEFAULT_CLOCK.get() {
+        Some(clock) => Arc::clone(clock),
+        None => Arc::new(SystemClock),
+    }
+}
+
+/// The current time from the default clock.
+#[cfg(any(feature = "compact", feature = "encrypted"))]
+pub(crate) fn now() -> Option<u64> {
+    match DEFAULT_CLOCK.get() {
+        Some(clock) => clock.now(),
+        None => SystemClock.now(),
+    }
+}
+
+/// The system clock. Reports `None` instead of panicking if it is set before 1970.
+///
+/// On `wasm32-unknown-unknown`, where `SystemTime` isn't available, it reads
+/// JavaScript's `Date.now()`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn now(&self) -> Option<u64> {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .map(|elapsed| elapsed.as_secs())
+    }
+
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    fn now(&self) -> Option<u64> {
+        let millis = js_sys::Date::now();
+        (millis >= 0.0).then(|| (millis / 1000.0) as u64)
+    }
+}
+
+/// A manually driven clock, e.g. to test expiry without sleeping.
+#[derive(Debug, Default)]
+pub struct FixedClock {
+    seconds: AtomicU64,
+}
+
+impl FixedClock {
+    pub fn new(seconds: u64) -> Self {
+        FixedClock {
+            seconds: AtomicU64::new(seconds),
+        }
+    }
+
+    pub fn set(&self, seconds: u64) {
+        self.seconds.store(seconds, Ordering::Relaxed);
+    }
+
+    pub fn advance(&self, seconds: u64) {
+        self.seconds.fetch_add(seconds, Ordering::Relaxed);
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> Option<u64> {
+        Some(self.seconds.load(Ordering::Relaxed))
+    }
+}
@@ -0,0 +1,481 @@
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use rand::Rng;
+
+use super::clock;
+use super::error::to_option;
+use super::hash::{hashes_equal, keyed_mac};
+#[cfg(feature = "stateless")]
+use super::jwt::mark_used;
+use super::{DEFAULT_LEEWAY, VerifyError};
+#[cfg(feature = "stateless")]
+use super::{ReplayGuard, TokenContext, TokenOptions};
+use crate::captcha::secure_rng;
+use crate::policy::edit_alphabet;
+use crate::{Captcha, MatchPolicy};
+
+/// Format version written as the first byte of compact tokens without options.
+const VERSION: u8 = 1;
+/// Format version of tokens with a flags byte after the version, announcing a
+/// minimum solve time and a context tag.
+const VERSION_FLAGS: u8 = 2;
+const FLAG_NBF: u8 = 1;
+const FLAG_CONTEXT: u8 = 2;
+const NONCE_LEN: usize = 8;
+const CONTEXT_TAG_LEN: usize = 8;
+const ANSWER_TAG_LEN: usize = 10;
+const MAC_LEN: usize = 12;
+/// Longest encoded match policy, whose length is stored in a single byte. Only
+/// confusable tables of non-ASCII characters can exceed it.
+const MAX_POLICY_LEN: usize = u8::MAX as usize;
+/// Version, expiry and nonce.
+const HEADER_LEN: usize = 1 + 4 + NONCE_LEN;
+
+const ANSWER_LABEL: &[u8] = b"captcha-rs/compact-answer/v1";
+const MAC_LABEL: &[u8] = b"captcha-rs/compact/v1";
+
+impl Captcha {
+    /// Sign a compact token: a binary alternative to `as_token` without JWT
+    /// headers or JSON claims, about 50 characters long with the default policy.
+    ///
+    /// Expiry is stored in 32 bits, so tokens can't expire after 2106. Returns
+    /// `None` if the clock can't be read, or if the encoded match policy is over
+    /// 255 bytes, which only happens with a large table of non-ASCII confusables.
+    pub fn as_compact_token(&self, secret: &str, expiration_seconds: u64) -> Option<String> {
+        self.compact_token(secret, clock::now()?, expiration_seconds, None, None)
+    }
+
+    pub fn as_compact_tuple(
+        &self,
+        secret: &str,
+        expiration_seconds: u64,
+    ) -> Option<(String, String)> {
+        self.as_compact_token(secret, expiration_seconds)
+            .map(|token| (self.to_base64(), token))
+    }
+
+    /// Sign a compact token bound to `context`, stamped with the clock of `options`
+    /// and carrying its minimum solve time, like `as_token_with_options`.
+    ///
+    /// The context is stored as a truncated keyed hash. A token with the default
+    /// context and no minimum solve time is as short as `as_compact_token`. Fails
+    /// in the same cases as `as_compact_token`.
+    #[cfg(feature = "stateless")]
+    pub fn as_compact_token_with(
+        &self,
+        secret: &str,
+        expiration_seconds: u64,
+        context: &TokenContext,
+        options: &TokenOptions,
+    ) -> Option<String> {
+        let now = options.now()?;
+        let min_solve_time = options.min_solve_seconds();
+        self.compact_token(
+            secret,
+            now,
+            expiration_seconds,
+            (min_solve_time > 0).then(|| now + min_solve_time),
+            context_tag(secret, context),
+        )
+    }
+
+    fn compact_token(
+        &self,
+        secret: &str,
+        now: u64,
+        expiration_seconds: u64,
+        nbf: Option<u64>,
+        context: Option<[u8; CONTEXT_TAG_LEN]>,
+    ) -> Option<String> {
+        let policy = self.match_policy.to_bytes();
+        if policy.len() > MAX_POLICY_LEN {
+            return None;
+        }
+        let exp = u32::try_from(now.checked_add(expiration_seconds)?).ok()?;
+        let nonce: [u8; NONCE_LEN] = secure_rng().random();
+        let flags = nbf.map_or(0, |_| FLAG_NBF) | context.map_or(0, |_| FLAG_CONTEXT);
+
+        let mut token =
+            Vec::with_capacity(HEADER_LEN + 13 + 1 + policy.len() + ANSWER_TAG_LEN + MAC_LEN);
+        if flags == 0 {
+            token.push(VERSION);
+        } else {
+            token.extend_from_slice(&[VERSION_FLAGS, flags]);
+        }
+        token.extend_from_slice(&exp.to_be_bytes());
+        token.extend_from_slice(&nonce);
+        if let Some(nbf) = nbf {
+            token.extend_from_slice(&u32::try_from(nbf).ok()?.to_be_bytes());
+        }
+        if let Some(context) = context {
+            token.extend_from_slice(&context);
+        }
+        token.push(policy.len() as u8);
+        token.extend_from_slice(&policy);
+        token.extend_from_slice(&answer_tag(
+            secret.as_bytes(),
+            &nonce,
+            &self.match_policy.normalize(&self.text),
+        ));
+        let mac = keyed_mac(secret.as_bytes(), MAC_LABEL, &[&token]);
+        token.extend_from_slice(&mac[..MAC_LEN]);
+
+        Some(URL_SAFE_NO_PAD.encode(token))
+    }
+}
+
+/// Truncated HMAC of the normalized answer, salted with the token nonce.
+fn answer_tag(secret: &[u8], nonce: &[u8], normalized_answer: &str) -> [u8; ANSWER_TAG_LEN] {
+    let mac = keyed_mac(secret, ANSWER_LABEL, &[nonce, normalized_answer.as_bytes()]);
+    let mut tag = [0; ANSWER_TAG_LEN];
+    tag.copy_from_slice(&mac[..ANSWER_TAG_LEN]);
+    tag
+}
+
+/// Truncated keyed hash of `context`, or `None` for the default context.
+#[cfg(feature = "stateless")]
+fn context_tag(secret: &str, context: &TokenContext) -> Option<[u8; CONTEXT_TAG_LEN]> {
+    if *context == TokenContext::default() {
+        return None;
+    }
+    let digest = context.digest(secret.as_bytes());
+    let mut tag = [0; CONTEXT_TAG_LEN];
+    tag.copy_from_slice(&digest[..CONTEXT_TAG_LEN]);
+    Some(tag)
+}
+
+/// The signed contents of a compact token.
+struct CompactClaims {
+    exp: u64,
+    nbf: Option<u64>,
+    nonce: [u8; NONCE_LEN],
+    context: Option<[u8; CONTEXT_TAG_LEN]>,
+    policy: MatchPolicy,
+    tag: [u8; ANSWER_TAG_LEN],
+}
+
+/// Verify a compact token, with the same results as `verify` for JWT tokens.
+pub fn verify_compact(token: &str, provided_solution: &str, secret: &str) -> Option<bool> {
+    to_option(verify_compact_detailed(token, provided_solution, secret))
+}
+
+/// Verify a compact token, reporting why it was rejected.
+///
+/// Tokens bound to a context only verify with [`verify_compact_with`].
+pub fn verify_compact_detailed(
+    token: &str,
+    provided_solution: &str,
+    secret: &str,
+) -> Result<(), VerifyError> {
+    let now = clock::now().ok_or(VerifyError::ClockUnavailable)?;
+    let claims = open(token, secret, None, now, DEFAULT_LEEWAY)?;
+    check_answer(&claims, secret, provided_solution)
+}
+
+/// Verify a compact token against `context` and the clock and leeway of `options`,
+/// like `verify_with_options` for JWT tokens.
+///
+/// With a `guard`, the token is also marked as used like [`super::verify_once`],
+/// so it can't be submitted again.
+#[cfg(feature = "stateless")]
+pub fn verify_compact_with(
+    token: &str,
+    provided_solution: &str,
+    secret: &str,
+    context: &TokenContext,
+    options: &TokenOptions,
+    guard: Option<&dyn ReplayGuard>,
+) -> Result<(), VerifyError> {
+    let now = options.now().ok_or(VerifyError::ClockUnavailable)?;
+    let claims = open(
+        token,
+        secret,
+        context_tag(secret, context),
+        now,
+        options.leeway_seconds(),
+    )?;
+    if let Some(guard) = guard
+        && !mark_used(
+            guard,
+            &URL_SAFE_NO_PAD.encode(claims.nonce),
+            claims.exp,
+            options,
+        )?
+    {
+        return Err(VerifyError::Replayed);
+    }
+    check_answer(&claims, secret, provided_solution)
+}
+
+/// Decode a token and check its signature, time and context, but not the answer.
+fn open(
+    token: &str,
+    secret: &str,
+    context: Option<[u8; CONTEXT_TAG_LEN]>,
+    now: u64,
+    leeway: u64,
+) -> Result<CompactClaims, VerifyError> {
+    let bytes = URL_SAFE_NO_PAD
+        .decode(token)
+        .map_err(|_| VerifyError::Malformed)?;
+    let header_len = match bytes.first() {
+        Some(&VERSION) => HEADER_LEN,
+        Some(&VERSION_FLAGS) => HEADER_LEN + 1,
+        _ => return Err(VerifyError::Malformed),
+    };
+    if bytes.len() < header_len + 1 + ANSWER_TAG_LEN + MAC_LEN {
+        return Err(VerifyError::Malformed);
+    }
+
+    let (signed, mac) = bytes.split_at(bytes.len() - MAC_LEN);
+    let expected_mac = keyed_mac(secret.as_bytes(), MAC_LABEL, &[signed]);
+    if !hashes_equal(mac, &expected_mac[..MAC_LEN]) {
+        return Err(VerifyError::InvalidSignature);
+    }
+    let claims = parse(signed).ok_or(VerifyError::Malformed)?;
+
+    if claims.exp.saturating_add(leeway) < now {
+        return Err(VerifyError::Expired);
+    }
+    if claims.nbf.is_some_and(|nbf| now < nbf) {
+        return Err(VerifyError::NotYetValid);
+    }
+    let context_matches = match (&claims.context, &context) {
+        (None, None) => true,
+        (Some(actual), Some(expected)) => hashes_equal(actual, expected),
+        _ => false,
+    };
+    if !context_matches {
+        return Err(VerifyError::ContextMismatch);
+    }
+    Ok(claims)
+}
+
+fn parse(signed: &[u8]) -> Option<CompactClaims> {
+    let (&version, rest) = signed.split_first()?;
+    let (flags, rest) = match version {
+        VERSION => (0, rest),
+        _ => {
+            let (&flags, rest) = rest.split_first()?;
+            if flags == 0 || flags > (FLAG_NBF | FLAG_CONTEXT) {
+                return None;
+            }
+            (flags, rest)
+        }
+    };
+    let (exp, rest) = rest.split_first_chunk::<4>()?;
+    let (nonce, mut rest) = rest.split_first_chunk::<NONCE_LEN>()?;
+    let mut nbf = None;
+    if flags & FLAG_NBF != 0 {
+        let (value, tail) = rest.split_first_chunk::<4>()?;
+        nbf = Some(u32::from_be_bytes(*value) as u64);
+        rest = tail;
+    }
+    let mut context = None;
+    if flags & FLAG_CONTEXT != 0 {
+        let (value, tail) = rest.split_first_chunk::<CONTEXT_TAG_LEN>()?;
+        context = Some(*value);
+        rest = tail;
+    }
+    let (&policy_len, rest) = rest.split_first()?;
+    if rest.len() != policy_len as usize + ANSWER_TAG_LEN {
+        return None;
+    }
+    let (policy, tag) = rest.split_at(policy_len as usize);
+
+    Some(CompactClaims {
+        exp: u32::from_be_bytes(*exp) as u64,
+        nbf,
+        nonce: *nonce,
+        context,
+        policy: MatchPolicy::from_bytes(policy)?,
+        tag: tag.try_into().ok()?,
+    })
+}
+
+fn check_answer(
+    claims: &CompactClaims,
+    secret: &str,
+    provided_solution: &str,
+) -> Result<(), VerifyError> {
+    let mut matched = false;
+    // Check every candidate, so the time taken doesn't reveal which one matched
+    for candidate in claims
+        .policy
+        .candidates(provided_solution, &edit_alphabet())
+    {
+        matched |= hashes_equal(
+            &claims.tag,
+            &answer_tag(secret.as_bytes(), &claims.nonce, &candidate),
+        );
+    }
+    if matched {
+        Ok(())
+    } else {
+        Err(VerifyError::WrongAnswer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CaptchaBuilder, MAX_CONFUSABLES};
+
+    fn captcha() -> Captcha {
+        CaptchaBuilder::new().text(String::from("SmaLl")).build()
+    }
+
+    #[test]
+    fn it_issues_short_tokens() {
+        let secret = "compact-secret";
+        let token = captcha().as_compact_token(secret, 300).unwrap();
+        assert!(token.len() < 100, "{}", token.len());
+        assert!(!token.contains('.'));
+
+        assert_eq!(verify_compact(&token, "small", secret), Some(true));
+        assert_eq!(verify_compact(&token, "wrong", secret), Some(false));
+        assert_eq!(verify_compact(&token, "small", "other-secret"), None);
+    }
+
+    #[test]
+    fn it_reports_why_a_token_was_rejected() {
+        let secret = "compact-secret";
+        let token = captcha().as_compact_token(secret, 300).unwrap();
+
+        let mut bytes = URL_SAFE_NO_PAD.decode(&token).unwrap();
+        bytes[HEADER_LEN + 3] ^= 1;
+        assert_eq!(
+            verify_compact_detailed(&URL_SAFE_NO_PAD.encode(&bytes), "small", secret),
+            Err(VerifyError::InvalidSignature)
+        );
+        assert_eq!(
+            verify_compact_detailed("%%", "small", secret),
+            Err(VerifyError::Malformed)
+        );
+        assert_eq!(
+            verify_compact_detailed(&token[..20], "small", secret),
+            Err(VerifyError::Malformed)
+        );
+
+        // Re-sign an expired token
+        let mut bytes = URL_SAFE_NO_PAD.decode(&token).unwrap();
+        bytes[1..5].copy_from_slice(&1u32.to_be_bytes());
+        let signed_len = bytes.len() - MAC_LEN;
+        let mac = keyed_mac(secret.as_bytes(), MAC_LABEL, &[&bytes[..signed_len]]);
+        bytes[signed_len..].copy_from_slice(&mac[..MAC_LEN]);
+        assert_eq!(
+            verify_compact_detailed(&URL_SAFE_NO_PAD.encode(&bytes), "small", secret),
+            Err(VerifyError::Expired)
+        );
+    }
+
+    #[test]
+    fn it_carries_the_match_policy() {
+        let secret = "compact-secret";
+        let captcha = CaptchaBuilder::new()
+            .text(String::from("SmaLl"))
+            .match_policy(MatchPolicy::new().case_sensitive(true).edit_distance(1))
+            .build();
+        let token = captcha.as_compact_token(secret, 300).unwrap();
+
+        assert_eq!(verify_compact(&token, "SmaL", secret), Some(true));
+        assert_eq!(verify_compact(&token, "small", secret), Some(false));
+    }
+
+    #[test]
+    fn it_rejects_policies_too_long_to_encode() {
+        let secret = "compact-secret";
+        let issue = |policy: MatchPolicy| {
+            CaptchaBuilder::new()
+                .text(String::from("SmaLl"))
+                .match_policy(policy)
+                .build()
+                .as_compact_token(secret, 300)
+        };
+
+        // A full table of ASCII confusables fits
+        let ascii = ('!'..)
+            .take(MAX_CONFUSABLES)
+            .fold(MatchPolicy::new(), |policy, c| policy.confusable(c, 'x'));
+        assert_eq!(ascii.to_bytes().len(), 2 + 2 * MAX_CONFUSABLES);
+        let token = issue(ascii).unwrap();
+        assert_eq!(verify_compact(&token, "small", secret), Some(true));
+
+        // Four-byte characters don't
+        let wide = ('😀'..)
+            .take(MAX_CONFUSABLES)
+            .fold(MatchPolicy::new(), |policy, c| policy.confusable(c, '😀'));
+        assert!(wide.to_bytes().len() > MAX_POLICY_LEN);
+        assert_eq!(issue(wide), None);
+    }
+
+    #[cfg(feature = "stateless")]
+    #[test]
+    fn it_verifies_with_context_options_and_guard() {
+        use crate::stateless::{FixedClock, MemoryReplayGuard};
+        use std::sync::Arc;
+
+        let secret = "compact-secret";
+        let clock = Arc::new(FixedClock::new(1_000_000));
+        let options = TokenOptions::new()
+            .clock(clock.clone())
+            .leeway(5)
+            .min_solve_time(2);
+        let signup = TokenContext::new()
+            .audience("signup")
+            .client_ip("203.0.113.7");
+        let guard = MemoryReplayGuard::new();
+        let token = captcha()
+            .as_compact_token_with(secret, 60, &signup, &options)
+            .unwrap();
+        assert!(token.len() < 100, "{}", token.len());
+
+        let verify = |context: &TokenContext| {
+            verify_compact_with(&token, "small", secret, context, &options, Some(&guard))
+        };
+        assert_eq!(verify(&signup), Err(VerifyError::NotYetValid));
+        clock.advance(2);
+        assert_eq!(
+            verify(&TokenContext::new().audience("login")),
+            Err(VerifyError::ContextMismatch)
+        );
+        assert_eq!(
+            verify(&TokenContext::default()),
+            Err(VerifyError::ContextMismatch)
+        );
+        assert_eq!(verify(&signup), Ok(()));
+        assert_eq!(verify(&signup), Err(VerifyError::Replayed));
+
+        // Plain verification only accepts tokens without a context
+        let bound = captcha()
+            .as_compact_token_with(secret, 60, &signup, &TokenOptions::new())
+            .unwrap();
+        assert_eq!(
+            verify_compact_detailed(&bound, "small", secret),
+            Err(VerifyError::ContextMismatch)
+        );
+
+        // The leeway of `options` applies rather than `DEFAULT_LEEWAY`
+        let token = captcha()
+            .as_compact_token_with(
+                secret,
+                60,
+                &TokenContext::default(),
+                &TokenOptions::new().clock(clock.clone()),
+            )
+            .unwrap();
+        assert_eq!(URL_SAFE_NO_PAD.decode(&token).unwrap()[0], VERSION);
+        clock.advance(66);
+        assert_eq!(
+            verify_compact_with(
+                &token,
+                "small",
+                secret,
+                &TokenContext::default(),
+                &options,
+                None
+            ),
+            Err(VerifyError::Expired)
+        );
+    }
+}
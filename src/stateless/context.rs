@@ -0,0 +1,119 @@
+use serde::{Deserialize, Serialize};
+
+use super::hash::keyed_hash;
+#[cfg(feature = "compact")]
+use super::hash::keyed_mac;
+
+const SESSION_LABEL: &[u8] = b"captcha-rs/session/v1";
+const CLIENT_IP_LABEL: &[u8] = b"captcha-rs/client-ip/v1";
+#[cfg(feature = "compact")]
+const CONTEXT_LABEL: &[u8] = b"captcha-rs/context/v1";
+
+/// Values a stateless token is bound to when issued and that must match on verify.
+///
+/// The audience (e.g. a form id such as `"signup"`) and issuer are stored as is.
+/// Session ids and client IPs are only stored as peppered hashes, so they can't be
+/// read back from the token.
+///
+/// ```rust
+/// use captcha_rs::{CaptchaBuilder, Key, KeySet, TokenContext, VerifyError, verify_with_context};
+///
+/// let keys = KeySet::new(Key::hmac("2026-01", "your-very-secure-secret"));
+/// let reset = TokenContext::new().audience("password-reset").session("session-id");
+///
+/// let captcha = CaptchaBuilder::new().build();
+/// let token = captcha.as_token_with_context(&keys, 300, &reset).unwrap();
+///
+/// assert_eq!(verify_with_context(&token, &captcha.text, &keys, &reset), Ok(()));
+///
+/// let signup = TokenContext::new().audience("signup").session("session-id");
+/// assert_eq!(
+///     verify_with_context(&token, &captcha.text, &keys, &signup),
+///     Err(VerifyError::ContextMismatch)
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TokenContext {
+    audience: Option<String>,
+    issuer: Option<String>,
+    session: Option<String>,
+    client_ip: Option<String>,
+}
+
+impl TokenContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// What the captcha protects, e.g. a form id or purpose such as `"signup"`.
+    pub fn audience(mut self, audience: impl Into<String>) -> Self {
+        self.audience = Some(audience.into());
+        self
+    }
+
+    /// The service issuing the token.
+    pub fn issuer(mut self, issuer: impl Into<String>) -> Self {
+        self.issuer = Some(issuer.into());
+        self
+    }
+
+    /// The client session id, stored hashed.
+    pub fn session(mut self, session: impl Into<String>) -> Self {
+        self.session = Some(session.into());
+        self
+    }
+
+    /// The client IP address, stored hashed.
+    pub fn client_ip(mut self, client_ip: impl Into<String>) -> Self {
+        self.client_ip = Some(client_ip.into());
+        self
+    }
+
+    pub(crate) fn claims(&self, pepper: &[u8]) -> ContextClaims {
+        ContextClaims {
+            aud: self.audience.clone(),
+            iss: self.issuer.clone(),
+            sid: self
+                .session
+                .as_deref()
+                .map(|value| keyed_hash(pepper, SESSION_LABEL, &[value.as_bytes()])),
+            cip: self
+                .client_ip
+                .as_deref()
+                .map(|value| keyed_hash(pepper, CLIENT_IP_LABEL, &[value.as_bytes()])),
+        }
+    }
+
+    /// Keyed hash over every value, binding a compact token to this context.
+    ///
+    /// Each value is prefixed with its presence and length, so different contexts
+    /// never hash the same input.
+    #[cfg(feature = "compact")]
+    pub(crate) fn digest(&self, pepper: &[u8]) -> [u8; 32] {
+        let mut parts = Vec::new();
+        for value in [&self.audience, &self.issuer, &self.session, &self.client_ip] {
+            match value {
+                Some(value) => {
+                    parts.push([&[1][..], &(value.len() as u64).to_be_bytes()].concat());
+                    parts.push(value.as_bytes().to_vec());
+                }
+                None => parts.push(vec![0]),
+            }
+        }
+        let parts: Vec<&[u8]> = parts.iter().map(Vec::as_slice).collect();
+        keyed_mac(pepper, CONTEXT_LABEL, &parts)
+    }
+}
+
+/// The binding claims written into a token. Absent values are left out.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct ContextClaims {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    aud: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    iss: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cip: Option<String>,
+}
@@ -0,0 +1,97 @@
+use std::fmt;
+
+/// Why a stateless captcha token was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum VerifyError {
+    /// The token expired. Show a new captcha.
+    Expired,
+    /// The signature doesn't match any known key: the token was tampered with,
+    /// signed with another secret or names an unknown key id.
+    InvalidSignature,
+    /// The token can't be decoded.
+    Malformed,
+    /// The token is valid but the provided solution is wrong.
+    WrongAnswer,
+    /// The token isn't valid yet.
+    NotYetValid,
+    /// The token was already used.
+    Replayed,
+    /// The token was issued for another audience, issuer, session or client.
+    ContextMismatch,
+    /// The current time couldn't be read.
+    ClockUnavailable,
+    /// Too many attempts for this challenge or client. Retry after the given
+    /// number of seconds.
+    LockedOut { retry_after: u64 },
+}
+
+impl VerifyError {
+    /// Whether the token itself is genuine, i.e. the user simply needs a new captcha
+    /// or another attempt rather than the request being forged.
+    pub fn is_genuine(&self) -> bool {
+        matches!(
+            self,
+            VerifyError::Expired
+                | VerifyError::WrongAnswer
+                | VerifyError::NotYetValid
+                | VerifyError::Replayed
+        )
+    }
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::Expired => f.write_str("captcha token expired"),
+            VerifyError::InvalidSignature => f.write_str("captcha token signature is invalid"),
+            VerifyError::Malformed => f.write_str("captcha token is malformed"),
+            VerifyError::WrongAnswer => f.write_str("captcha answer is wrong"),
+            VerifyError::NotYetValid => f.write_str("captcha token is not valid yet"),
+            VerifyError::Replayed => f.write_str("captcha token was already used"),
+            VerifyError::ContextMismatch => {
+                f.write_str("captcha token was issued for another context")
+            }
+            VerifyError::ClockUnavailable => f.write_str("current time is unavailable"),
+            VerifyError::LockedOut { retry_after } => write!(
+                f,
+                "too many captcha attempts, retry in {} seconds",
+                retry_after
+            ),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// Collapse a detailed result into the `Option<bool>` returned by the `verify`
+/// functions: `Some(false)` on a wrong answer and `None` for any other error.
+pub(crate) fn to_option(result: Result<(), VerifyError>) -> Option<bool> {
+    match result {
+        Ok(()) => Some(true),
+        Err(VerifyError::WrongAnswer) => Some(false),
+        Err(_) => None,
+    }
+}
+
+#[cfg(feature = "stateless")]
+impl From<jsonwebtoken::errors::Error> for VerifyError {
+    fn from(error: jsonwebtoken::errors::Error) -> Self {
+        use jsonwebtoken::errors::ErrorKind;
+
+        match error.kind() {
+            ErrorKind::ExpiredSignature => VerifyError::Expired,
+            ErrorKind::ImmatureSignature => VerifyError::NotYetValid,
+            ErrorKind::InvalidSignature
+            | ErrorKind::InvalidAlgorithm
+            | ErrorKind::InvalidAlgorithmName
+            | ErrorKind::InvalidKeyFormat
+            | ErrorKind::InvalidEcdsaKey
+            | ErrorKind::InvalidRsaKey(_) => VerifyError::InvalidSignature,
+            ErrorKind::InvalidAudience | ErrorKind::InvalidIssuer | ErrorKind::InvalidSubject => {
+                VerifyError::ContextMismatch
+            }
+            _ => VerifyError::Malformed,
+        }
+    }
+}
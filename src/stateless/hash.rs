@@ -0,0 +1,91 @@
+#[cfg(feature = "stateless")]
+use base64::Engine;
+#[cfg(feature = "stateless")]
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use hmac::{Hmac, Mac};
+#[cfg(feature = "stateless")]
+use sha2::Digest;
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+
+/// Version written into the `v` claim of new JWT tokens.
+///
+/// Tokens without `v` were issued before salted HMAC hashes and still verify with
+/// [`legacy_answer_hash`] until they expire.
+#[cfg(feature = "stateless")]
+pub(crate) const TOKEN_VERSION: u8 = 1;
+
+/// Length in bytes of the random per-token salt.
+#[cfg(feature = "stateless")]
+pub(crate) const SALT_LEN: usize = 16;
+
+#[cfg(feature = "stateless")]
+const ANSWER_LABEL: &[u8] = b"captcha-rs/answer/v1";
+
+/// HMAC-SHA256 keyed by the pepper over `label || 0x00 || parts...`.
+///
+/// The label separates the uses of one pepper, and the fixed-length salt in front of
+/// the answer keeps the message unambiguous.
+pub(crate) fn keyed_mac(pepper: &[u8], label: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(pepper).expect("HMAC accepts any key length");
+    mac.update(label);
+    mac.update(&[0]);
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+/// [`keyed_mac`] as URL-safe base64.
+#[cfg(feature = "stateless")]
+pub(crate) fn keyed_hash(pepper: &[u8], label: &[u8], parts: &[&[u8]]) -> String {
+    URL_SAFE_NO_PAD.encode(keyed_mac(pepper, label, parts))
+}
+
+/// Hash of a normalized answer for version 1 tokens.
+#[cfg(feature = "stateless")]
+pub(crate) fn answer_hash(pepper: &[u8], salt: &[u8; SALT_LEN], normalized_answer: &str) -> String {
+    keyed_hash(pepper, ANSWER_LABEL, &[salt, normalized_answer.as_bytes()])
+}
+
+/// Unsalted `SHA-256(pepper || answer)` used by tokens without a version.
+#[cfg(feature = "stateless")]
+pub(crate) fn legacy_answer_hash(pepper: &[u8], normalized_answer: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(pepper);
+    hasher.update(normalized_answer.as_bytes());
+    URL_SAFE_NO_PAD.encode(hasher.finalize())
+}
+
+/// Compare two hashes without leaking the position of the first difference.
+pub(crate) fn hashes_equal(a: &[u8], b: &[u8]) -> bool {
+    a.ct_eq(b).into()
+}
+
+#[cfg(all(test, feature = "stateless"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_separates_domains_and_salts() {
+        let salt = [1; SALT_LEN];
+        let hash = answer_hash(b"pepper", &salt, "abc");
+        assert_eq!(hash, answer_hash(b"pepper", &salt, "abc"));
+        assert_ne!(hash, answer_hash(b"pepper", &[2; SALT_LEN], "abc"));
+        assert_ne!(hash, answer_hash(b"other", &salt, "abc"));
+        assert_ne!(
+            keyed_hash(b"pepper", b"label-a", &[b"abc"]),
+            keyed_hash(b"pepper", b"label-b", &[b"abc"])
+        );
+        // No ambiguity between the pepper and the answer, unlike the legacy hash
+        assert_eq!(
+            legacy_answer_hash(b"ab", "c"),
+            legacy_answer_hash(b"a", "bc")
+        );
+        assert!(hashes_equal(hash.as_bytes(), hash.clone().as_bytes()));
+        assert!(!hashes_equal(
+            hash.as_bytes(),
+            legacy_answer_hash(b"pepper", "abc").as_bytes()
+        ));
+    }
+}
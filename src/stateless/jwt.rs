@@ -0,0 +1,764 @@
+use jsonwebtoken::{Header, Validation, decode, decode_header, encode};
+use rand::Rng;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use super::context::ContextClaims;
+use super::error::to_option;
+use super::hash::{SALT_LEN, TOKEN_VERSION, answer_hash, hashes_equal, legacy_answer_hash};
+use super::{Key, KeySet, ReplayGuard, TokenContext, TokenOptions, VerifyError};
+use crate::captcha::secure_rng;
+use crate::policy::edit_alphabet;
+use crate::{Captcha, MatchPolicy};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct Claims {
+    /// Token format version, absent from tokens hashed with the legacy scheme.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    v: Option<u8>,
+    pub(super) hash: String,
+    /// Random salt of the answer hash.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    salt: Option<String>,
+    pub(super) exp: usize,
+    /// Earliest time an answer is accepted, set by `TokenOptions::min_solve_time`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    nbf: Option<u64>,
+    /// Random challenge id, absent from tokens issued before replay protection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) jti: Option<String>,
+    /// The `MatchPolicy` the answer was hashed with, absent for the default policy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pol: Option<String>,
+    #[serde(flatten)]
+    context: ContextClaims,
+}
+
+impl Claims {
+    fn policy(&self) -> Result<MatchPolicy, VerifyError> {
+        let Some(pol) = &self.pol else {
+            return Ok(MatchPolicy::default());
+        };
+        base64::Engine::decode(&base64::engine::general_purpose::URL_SAFE_NO_PAD, pol)
+            .ok()
+            .and_then(|bytes| MatchPolicy::from_bytes(&bytes))
+            .ok_or(VerifyError::Malformed)
+    }
+
+    /// Whether `normalized_answer` hashes to the `hash` claim.
+    fn hash_matches(&self, pepper: &[u8], normalized_answer: &str) -> Result<bool, VerifyError> {
+        let expected = match self.v {
+            None => legacy_answer_hash(pepper, normalized_answer),
+            Some(TOKEN_VERSION) => {
+                let salt = self.salt.as_deref().ok_or(VerifyError::Malformed)?;
+                let salt: [u8; SALT_LEN] =
+                    base64::Engine::decode(&base64::engine::general_purpose::URL_SAFE_NO_PAD, salt)
+                        .ok()
+                        .and_then(|salt| salt.try_into().ok())
+                        .ok_or(VerifyError::Malformed)?;
+                answer_hash(pepper, &salt, normalized_answer)
+            }
+            Some(_) => return Err(VerifyError::Malformed),
+        };
+        Ok(hashes_equal(self.hash.as_bytes(), expected.as_bytes()))
+    }
+}
+
+impl Captcha {
+    pub fn as_token(&self, secret: &str, expiration_seconds: u64) -> Option<String> {
+        self.sign(
+            &Key::unnamed(secret),
+            expiration_seconds,
+            &TokenContext::default(),
+            &TokenOptions::default(),
+        )
+    }
+
+    pub fn as_tuple(&self, secret: &str, expiration_seconds: u64) -> Option<(String, String)> {
+        self.as_token(secret, expiration_seconds)
+            .map(|token| (self.to_base64(), token))
+    }
+
+    /// Sign a token with the current key of `keys`, writing its `kid` into the header.
+    ///
+    /// Returns `None` if the current key is verification-only.
+    pub fn as_token_with_keys(&self, keys: &KeySet, expiration_seconds: u64) -> Option<String> {
+        self.as_token_with_context(keys, expiration_seconds, &TokenContext::default())
+    }
+
+    pub fn as_tuple_with_keys(
+        &self,
+        keys: &KeySet,
+        expiration_seconds: u64,
+    ) -> Option<(String, String)> {
+        self.as_token_with_keys(keys, expiration_seconds)
+            .map(|token| (self.to_base64(), token))
+    }
+
+    /// Sign a token bound to `context`. It then only verifies with the same context.
+    pub fn as_token_with_context(
+        &self,
+        keys: &KeySet,
+        expiration_seconds: u64,
+        context: &TokenContext,
+    ) -> Option<String> {
+        self.as_token_with_options(keys, expiration_seconds, context, &TokenOptions::default())
+    }
+
+    pub fn as_tuple_with_context(
+        &self,
+        keys: &KeySet,
+        expiration_seconds: u64,
+        context: &TokenContext,
+    ) -> Option<(String, String)> {
+        self.as_token_with_context(keys, expiration_seconds, context)
+            .map(|token| (self.to_base64(), token))
+    }
+
+    /// Sign a token bound to `context`, stamped with the clock of `options` and
+    /// carrying its minimum solve time.
+    ///
+    /// Returns `None` if the clock can't be read.
+    pub fn as_token_with_options(
+        &self,
+        keys: &KeySet,
+        expiration_seconds: u64,
+        context: &TokenContext,
+        options: &TokenOptions,
+    ) -> Option<String> {
+        self.sign(keys.current(), expiration_seconds, context, options)
+    }
+
+    fn sign(
+        &self,
+        key: &Key,
+        expiration_seconds: u64,
+        context: &TokenContext,
+        options: &TokenOptions,
+    ) -> Option<String> {
+        let now = options.now()?;
+        let exp = now.checked_add(expiration_seconds)?;
+        let min_solve_time = options.min_solve_seconds();
+
+        let salt: [u8; SALT_LEN] = secure_rng().random();
+
+        let claims = Claims {
+            v: Some(TOKEN_VERSION),
+            hash: answer_hash(
+                key.pepper(),
+                &salt,
+                &self.match_policy.normalize(&self.text),
+            ),
+            salt: Some(base64::Engine::encode(
+                &base64::engine::general_purpose::URL_SAFE_NO_PAD,
+                salt,
+            )),
+            exp: exp as usize,
+            nbf: (min_solve_time > 0).then(|| now + min_solve_time),
+            jti: Some(random_id()),
+            pol: (self.match_policy != MatchPolicy::default()).then(|| {
+                base64::Engine::encode(
+                    &base64::engine::general_purpose::URL_SAFE_NO_PAD,
+                    self.match_policy.to_bytes(),
+                )
+            }),
+            context: context.claims(key.pepper()),
+        };
+
+        encode_with_key(key, &claims)
+    }
+}
+
+/// Sign `claims` with `key`, writing its `kid` into the header.
+pub(super) fn encode_with_key<T: Serialize>(key: &Key, claims: &T) -> Option<String> {
+    let mut header = Header::new(key.algorithm());
+    header.kid = key.kid().map(String::from);
+
+    encode(&header, claims, key.encoding_key()?).ok()
+}
+
+/// Random URL-safe challenge id.
+fn random_id() -> String {
+    base64::Engine::encode(
+        &base64::engine::general_purpose::URL_SAFE_NO_PAD,
+        secure_rng().random::<[u8; 16]>(),
+    )
+}
+
+/// Thin wrapper over [`verify_detailed`]: `Some(true)` if valid, `Some(false)` on a
+/// wrong answer and `None` for any other error.
+pub fn verify(token: &str, provided_solution: &str, secret: &str) -> Option<bool> {
+    to_option(verify_detailed(token, provided_solution, secret))
+}
+
+/// Verify a token, reporting why it was rejected.
+pub fn verify_detailed(
+    token: &str,
+    provided_solution: &str,
+    secret: &str,
+) -> Result<(), VerifyError> {
+    let key = Key::unnamed(secret);
+    let claims = decode_claims(token, &key)?;
+    check_time(&claims, &TokenOptions::default())?;
+    check_context(&claims, &key, &TokenContext::default())?;
+    check_answer(&claims, &key, provided_solution)
+}
+
+/// Thin wrapper over [`verify_detailed_with_keys`].
+pub fn verify_with_keys(token: &str, provided_solution: &str, keys: &KeySet) -> Option<bool> {
+    to_option(verify_detailed_with_keys(token, provided_solution, keys))
+}
+
+/// Verify a token against the key named by its `kid` header.
+///
+/// Tokens without a `kid` (from `Captcha::as_token`) are tried against every key,
+/// so a plain secret can be moved into a `KeySet` without invalidating them.
+/// Tokens bound to a context are rejected, use [`verify_with_context`] for them.
+pub fn verify_detailed_with_keys(
+    token: &str,
+    provided_solution: &str,
+    keys: &KeySet,
+) -> Result<(), VerifyError> {
+    verify_with_context(token, provided_solution, keys, &TokenContext::default())
+}
+
+/// Verify a token issued with `Captcha::as_token_with_context`.
+///
+/// Every context value must equal the one the token was issued with: a value set
+/// on only one side is a [`VerifyError::ContextMismatch`] as well.
+pub fn verify_with_context(
+    token: &str,
+    provided_solution: &str,
+    keys: &KeySet,
+    context: &TokenContext,
+) -> Result<(), VerifyError> {
+    verify_with_options(
+        token,
+        provided_solution,
+        keys,
+        context,
+        &TokenOptions::default(),
+    )
+}
+
+/// Verify a token against the clock and leeway of `options`.
+///
+/// Expiry and the minimum solve time are checked here rather than by the JWT
+/// library, so tests and verifiers can supply their own `Clock`.
+pub fn verify_with_options(
+    token: &str,
+    provided_solution: &str,
+    keys: &KeySet,
+    context: &TokenContext,
+    options: &TokenOptions,
+) -> Result<(), VerifyError> {
+    let (claims, key) = decode_with_keys(token, keys)?;
+    check_time(&claims, options)?;
+    check_context(&claims, key, context)?;
+    check_answer(&claims, key, provided_solution)
+}
+
+/// Verify a token and mark it as used, so it can't be submitted again.
+///
+/// Every attempt with a genuine token consumes it, including wrong answers, so a
+/// bot can't try several answers against one captcha. Tokens without a challenge
+/// id can't be tracked and are rejected as [`VerifyError::Malformed`].
+pub fn verify_once(
+    token: &str,
+    provided_solution: &str,
+    keys: &KeySet,
+    context: &TokenContext,
+    guard: &impl ReplayGuard,
+) -> Result<(), VerifyError> {
+    verify_once_with_options(
+        token,
+        provided_solution,
+        keys,
+        context,
+        &TokenOptions::default(),
+        guard,
+    )
+}
+
+/// [`verify_once`] against the clock and leeway of `options`. Used ids are kept
+/// until the token expires by that clock.
+pub fn verify_once_with_options(
+    token: &str,
+    provided_solution: &str,
+    keys: &KeySet,
+    context: &TokenContext,
+    options: &TokenOptions,
+    guard: &impl ReplayGuard,
+) -> Result<(), VerifyError> {
+    let (claims, key) = decode_with_keys(token, keys)?;
+    check_time(&claims, options)?;
+    check_context(&claims, key, context)?;
+    let id = claims.jti.as_deref().ok_or(VerifyError::Malformed)?;
+    if !mark_used(guard, id, claims.exp as u64, options)? {
+        return Err(VerifyError::Replayed);
+    }
+    check_answer(&claims, key, provided_solution)
+}
+
+pub(super) fn decode_with_keys<'a, T: DeserializeOwned>(
+    token: &str,
+    keys: &'a KeySet,
+) -> Result<(T, &'a Key), VerifyError> {
+    let header = decode_header(token)?;
+    match header.kid {
+        Some(kid) => {
+            let key = keys.get(&kid).ok_or(VerifyError::InvalidSignature)?;
+            Ok((decode_claims(token, key)?, key))
+        }
+        None => {
+            let mut error = VerifyError::InvalidSignature;
+            for key in keys.iter() {
+                match decode_claims(token, key) {
+                    Ok(claims) => return Ok((claims, key)),
+                    // Only a signature mismatch means another key may still match
+                    Err(VerifyError::InvalidSignature) => {}
+                    Err(other) => error = other,
+                }
+            }
+            Err(error)
+        }
+    }
+}
+
+fn decode_claims<T: DeserializeOwned>(token: &str, key: &Key) -> Result<T, VerifyError> {
+    let mut validation = Validation::new(key.algorithm());
+    // The audience is compared with the rest of the context in `check_context`
+    validation.validate_aud = false;
+    // Expiry and `nbf` are checked against the configured clock in `check_time`
+    validation.validate_exp = false;
+    validation.required_spec_claims.clear();
+    let token_data = decode::<T>(token, key.decoding_key(), &validation)?;
+    Ok(token_data.claims)
+}
+
+/// Mark a challenge as used until the last second `check_time` accepts it, going
+/// by the clock of `options`.
+pub(super) fn mark_used(
+    guard: &(impl ReplayGuard + ?Sized),
+    id: &str,
+    exp: u64,
+    options: &TokenOptions,
+) -> Result<bool, VerifyError> {
+    let now = options.now().ok_or(VerifyError::ClockUnavailable)?;
+    Ok(guard.mark_used(id, exp.saturating_add(options.leeway_seconds()), now))
+}
+
+pub(super) fn check_time(claims: &Claims, options: &TokenOptions) -> Result<(), VerifyError> {
+    let now = options.now().ok_or(VerifyError::ClockUnavailable)?;
+    if (claims.exp as u64).saturating_add(options.leeway_seconds()) < now {
+        return Err(VerifyError::Expired);
+    }
+    if claims.nbf.is_some_and(|nbf| now < nbf) {
+        return Err(VerifyError::NotYetValid);
+    }
+    Ok(())
+}
+
+pub(super) fn check_context(
+    claims: &Claims,
+    key: &Key,
+    context: &TokenContext,
+) -> Result<(), VerifyError> {
+    if claims.context == context.claims(key.pepper()) {
+        Ok(())
+    } else {
+        Err(VerifyError::ContextMismatch)
+    }
+}
+
+pub(super) fn check_answer(
+    claims: &Claims,
+    key: &Key,
+    provided_solution: &str,
+) -> Result<(), VerifyError> {
+    let policy = claims.policy()?;
+    let mut matched = false;
+    // Check every candidate, so the time taken doesn't reveal which one matched
+    for candidate in policy.candidates(provided_solution, &edit_alphabet()) {
+        matched |= claims.hash_matches(key.pepper(), &candidate)?;
+    }
+    if matched {
+        Ok(())
+    } else {
+        Err(VerifyError::WrongAnswer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CaptchaBuilder;
+    use crate::stateless::{Clock, FixedClock, MemoryReplayGuard, SystemClock};
+    use std::sync::Arc;
+
+    fn captcha() -> Captcha {
+        CaptchaBuilder::new().text(String::from("DeTail")).build()
+    }
+
+    fn now() -> u64 {
+        SystemClock.now().unwrap()
+    }
+
+    /// Sign arbitrary claims, e.g. to produce expired or immature tokens.
+    fn sign_claims<T: Serialize>(claims: &T, secret: &str) -> String {
+        encode(
+            &Header::default(),
+            claims,
+            &jsonwebtoken::EncodingKey::from_secret(secret.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn it_reports_why_a_token_was_rejected() {
+        let secret = "detailed-secret";
+        let token = captcha().as_token(secret, 60).unwrap();
+
+        assert_eq!(verify_detailed(&token, "detail", secret), Ok(()));
+        assert_eq!(
+            verify_detailed(&token, "nope", secret),
+            Err(VerifyError::WrongAnswer)
+        );
+        assert_eq!(
+            verify_detailed(&token, "detail", "other-secret"),
+            Err(VerifyError::InvalidSignature)
+        );
+        assert_eq!(
+            verify_detailed("garbage", "detail", secret),
+            Err(VerifyError::Malformed)
+        );
+    }
+
+    #[test]
+    fn it_reports_expired_and_immature_tokens() {
+        let secret = "detailed-secret";
+        let hash = legacy_answer_hash(secret.as_bytes(), "detail");
+
+        let expired = sign_claims(
+            &Claims {
+                v: None,
+                hash: hash.clone(),
+                salt: None,
+                exp: 1,
+                nbf: None,
+                jti: None,
+                pol: None,
+                context: ContextClaims::default(),
+            },
+            secret,
+        );
+        assert_eq!(
+            verify_detailed(&expired, "detail", secret),
+            Err(VerifyError::Expired)
+        );
+        assert!(VerifyError::Expired.is_genuine());
+
+        // Issued now with a minimum solve time, so `nbf` lies in the future
+        let clock = Arc::new(FixedClock::new(now()));
+        let options = TokenOptions::new().clock(clock.clone()).min_solve_time(300);
+        let keys = KeySet::new(Key::unnamed(secret));
+        let none = TokenContext::default();
+        let immature = captcha()
+            .as_token_with_options(&keys, 600, &none, &options)
+            .unwrap();
+        assert_eq!(
+            verify_with_options(&immature, "detail", &keys, &none, &options),
+            Err(VerifyError::NotYetValid)
+        );
+        assert_eq!(
+            verify_detailed(&immature, "detail", secret),
+            Err(VerifyError::NotYetValid)
+        );
+        assert!(VerifyError::NotYetValid.is_genuine());
+
+        clock.advance(300);
+        assert_eq!(
+            verify_with_options(&immature, "detail", &keys, &none, &options),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn it_keeps_the_option_wrappers() {
+        let secret = "detailed-secret";
+        let token = captcha().as_token(secret, 60).unwrap();
+        assert_eq!(verify(&token, "detail", secret), Some(true));
+        assert_eq!(verify(&token, "nope", secret), Some(false));
+        assert_eq!(verify(&token, "detail", "other-secret"), None);
+    }
+
+    #[test]
+    fn it_rejects_replayed_tokens() {
+        let keys = KeySet::new(Key::hmac("k1", "replay-secret"));
+        let guard = MemoryReplayGuard::new();
+        let none = TokenContext::default();
+
+        let token = captcha().as_token_with_keys(&keys, 60).unwrap();
+        assert_eq!(verify_once(&token, "detail", &keys, &none, &guard), Ok(()));
+        assert_eq!(
+            verify_once(&token, "detail", &keys, &none, &guard),
+            Err(VerifyError::Replayed)
+        );
+
+        // A wrong answer consumes the token as well
+        let token = captcha().as_token_with_keys(&keys, 60).unwrap();
+        assert_eq!(
+            verify_once(&token, "nope", &keys, &none, &guard),
+            Err(VerifyError::WrongAnswer)
+        );
+        assert_eq!(
+            verify_once(&token, "detail", &keys, &none, &guard),
+            Err(VerifyError::Replayed)
+        );
+        assert_eq!(guard.len(), 2);
+    }
+
+    #[test]
+    fn it_rejects_replays_against_the_configured_clock() {
+        let keys = KeySet::new(Key::hmac("k1", "replay-secret"));
+        let guard = MemoryReplayGuard::new();
+        let none = TokenContext::default();
+        let clock = Arc::new(FixedClock::new(1_000_000));
+        let options = TokenOptions::new().clock(clock.clone()).leeway(10);
+
+        let token = captcha()
+            .as_token_with_options(&keys, 60, &none, &options)
+            .unwrap();
+        clock.advance(65);
+        assert_eq!(
+            verify_once_with_options(&token, "detail", &keys, &none, &options, &guard),
+            Ok(())
+        );
+        assert_eq!(
+            verify_once_with_options(&token, "detail", &keys, &none, &options, &guard),
+            Err(VerifyError::Replayed)
+        );
+        clock.advance(6);
+        assert_eq!(
+            verify_once_with_options(&token, "detail", &keys, &none, &options, &guard),
+            Err(VerifyError::Expired)
+        );
+    }
+
+    #[test]
+    fn it_binds_tokens_to_their_context() {
+        let keys = KeySet::new(Key::hmac("k1", "context-secret"));
+        let reset = TokenContext::new()
+            .audience("password-reset")
+            .issuer("auth")
+            .session("session-1")
+            .client_ip("203.0.113.7");
+        let token = captcha().as_token_with_context(&keys, 60, &reset).unwrap();
+
+        assert_eq!(verify_with_context(&token, "detail", &keys, &reset), Ok(()));
+        assert_eq!(
+            verify_with_context(&token, "nope", &keys, &reset),
+            Err(VerifyError::WrongAnswer)
+        );
+
+        let mismatches = [
+            reset.clone().audience("signup"),
+            reset.clone().issuer("other"),
+            reset.clone().session("session-2"),
+            reset.clone().client_ip("198.51.100.1"),
+            TokenContext::new().audience("password-reset"),
+        ];
+        for context in &mismatches {
+            assert_eq!(
+                verify_with_context(&token, "detail", &keys, context),
+                Err(VerifyError::ContextMismatch),
+                "{:?}",
+                context
+            );
+        }
+
+        // A bound token can't be verified by ignoring its context
+        assert_eq!(verify_with_keys(&token, "detail", &keys), None);
+
+        // An unbound token can't satisfy a verifier expecting a context
+        let unbound = captcha().as_token_with_keys(&keys, 60).unwrap();
+        assert_eq!(
+            verify_with_context(&unbound, "detail", &keys, &reset),
+            Err(VerifyError::ContextMismatch)
+        );
+    }
+
+    #[test]
+    fn it_hashes_session_and_ip() {
+        let keys = KeySet::new(Key::hmac("k1", "context-secret"));
+        let context = TokenContext::new()
+            .session("session-secret-value")
+            .client_ip("203.0.113.7");
+        let token = captcha()
+            .as_token_with_context(&keys, 60, &context)
+            .unwrap();
+
+        let payload = token.split('.').nth(1).unwrap();
+        let payload =
+            base64::Engine::decode(&base64::engine::general_purpose::URL_SAFE_NO_PAD, payload)
+                .unwrap();
+        let payload = String::from_utf8(payload).unwrap();
+        assert!(!payload.contains("session-secret-value"));
+        assert!(!payload.contains("203.0.113.7"));
+    }
+
+    #[test]
+    fn it_verifies_with_the_issuing_match_policy() {
+        let keys = KeySet::new(Key::hmac("k1", "policy-secret"));
+        let policy = MatchPolicy::new()
+            .case_sensitive(true)
+            .trim(true)
+            .confusables(true)
+            .edit_distance(1);
+        let captcha = CaptchaBuilder::new()
+            .text(String::from("Ab0d1"))
+            .match_policy(policy)
+            .build();
+        let token = captcha.as_token_with_keys(&keys, 60).unwrap();
+
+        assert_eq!(verify_with_keys(&token, " AbOdl ", &keys), Some(true));
+        // One substitution, insertion or deletion
+        assert_eq!(verify_with_keys(&token, "AbXd1", &keys), Some(true));
+        assert_eq!(verify_with_keys(&token, "Ab0d1z", &keys), Some(true));
+        assert_eq!(verify_with_keys(&token, "Ab0d", &keys), Some(true));
+        // A case change counts as an edit, two edits are too many
+        assert_eq!(verify_with_keys(&token, "ab0d1", &keys), Some(true));
+        assert_eq!(verify_with_keys(&token, "aB0d1", &keys), Some(false));
+        assert_eq!(verify_with_keys(&token, "AbXd", &keys), Some(false));
+
+        // Oversized answers are rejected without expanding their candidates
+        let started = std::time::Instant::now();
+        let huge = "Ab0d1".repeat(400_000);
+        assert_eq!(verify_with_keys(&token, &huge, &keys), Some(false));
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+    }
+
+    #[test]
+    fn it_salts_every_token() {
+        let secret = "salted-secret";
+        let captcha = captcha();
+        let a = captcha.as_token(secret, 60).unwrap();
+        let b = captcha.as_token(secret, 60).unwrap();
+        let claims = |token: &str| decode_claims::<Claims>(token, &Key::unnamed(secret)).unwrap();
+
+        let (a, b) = (claims(&a), claims(&b));
+        assert_eq!(a.v, Some(TOKEN_VERSION));
+        assert_ne!(a.salt, b.salt);
+        assert_ne!(a.hash, b.hash);
+    }
+
+    #[test]
+    fn it_still_verifies_legacy_tokens() {
+        let secret = "legacy-secret";
+        let legacy = sign_claims(
+            &Claims {
+                v: None,
+                hash: legacy_answer_hash(secret.as_bytes(), "detail"),
+                salt: None,
+                exp: (now() + 60) as usize,
+                nbf: None,
+                jti: None,
+                pol: None,
+                context: ContextClaims::default(),
+            },
+            secret,
+        );
+        assert_eq!(verify(&legacy, "DETAIL", secret), Some(true));
+        assert_eq!(verify(&legacy, "nope", secret), Some(false));
+
+        // Tokens from a newer format are rejected
+        let unknown = sign_claims(
+            &Claims {
+                v: Some(TOKEN_VERSION + 1),
+                hash: legacy_answer_hash(secret.as_bytes(), "detail"),
+                salt: None,
+                exp: (now() + 60) as usize,
+                nbf: None,
+                jti: None,
+                pol: None,
+                context: ContextClaims::default(),
+            },
+            secret,
+        );
+        assert_eq!(
+            verify_detailed(&unknown, "detail", secret),
+            Err(VerifyError::Malformed)
+        );
+    }
+
+    #[test]
+    fn it_checks_expiry_against_the_configured_clock() {
+        let clock = Arc::new(FixedClock::new(1_000_000));
+        let options = TokenOptions::new().clock(clock.clone()).leeway(10);
+        let keys = KeySet::new(Key::hmac("k1", "clock-secret"));
+        let context = TokenContext::default();
+        let token = captcha()
+            .as_token_with_options(&keys, 60, &context, &options)
+            .unwrap();
+        let check = || verify_with_options(&token, "detail", &keys, &context, &options);
+
+        assert_eq!(check(), Ok(()));
+        clock.advance(70);
+        assert_eq!(check(), Ok(()));
+        clock.advance(1);
+        assert_eq!(check(), Err(VerifyError::Expired));
+
+        // The system clock says this token expired decades ago
+        assert_eq!(
+            verify_with_context(&token, "detail", &keys, &context),
+            Err(VerifyError::Expired)
+        );
+    }
+
+    #[test]
+    fn it_enforces_the_minimum_solve_time() {
+        let clock = Arc::new(FixedClock::new(1_000_000));
+        let options = TokenOptions::new()
+            .clock(clock.clone())
+            .leeway(30)
+            .min_solve_time(3);
+        let keys = KeySet::new(Key::hmac("k1", "clock-secret"));
+        let context = TokenContext::default();
+        let token = captcha()
+            .as_token_with_options(&keys, 60, &context, &options)
+            .unwrap();
+        let check = || verify_with_options(&token, "detail", &keys, &context, &options);
+
+        // The leeway doesn't shorten the minimum solve time
+        assert_eq!(check(), Err(VerifyError::NotYetValid));
+        clock.advance(2);
+        assert_eq!(check(), Err(VerifyError::NotYetValid));
+        clock.advance(1);
+        assert_eq!(check(), Ok(()));
+    }
+
+    #[test]
+    fn it_reports_an_unavailable_clock() {
+        struct BrokenClock;
+        impl Clock for BrokenClock {
+            fn now(&self) -> Option<u64> {
+                None
+            }
+        }
+
+        let keys = KeySet::new(Key::hmac("k1", "clock-secret"));
+        let context = TokenContext::default();
+        let broken = TokenOptions::new().clock(Arc::new(BrokenClock));
+        assert!(
+            captcha()
+                .as_token_with_options(&keys, 60, &context, &broken)
+                .is_none()
+        );
+
+        let token = captcha().as_token_with_keys(&keys, 60).unwrap();
+        assert_eq!(
+            verify_with_options(&token, "detail", &keys, &context, &broken),
+            Err(VerifyError::ClockUnavailable)
+        );
+    }
+}
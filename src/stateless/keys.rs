@@ -0,0 +1,269 @@
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey};
+use std::fmt;
+
+/// Asymmetric signature algorithms supported for stateless tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAlgorithm {
+    /// EdDSA over Curve25519.
+    Ed25519,
+    /// ECDSA over P-256 with SHA-256.
+    Es256,
+    /// RSASSA-PKCS1-v1_5 with SHA-256.
+    Rs256,
+}
+
+impl KeyAlgorithm {
+    fn jwt_algorithm(self) -> Algorithm {
+        match self {
+            KeyAlgorithm::Ed25519 => Algorithm::EdDSA,
+            KeyAlgorithm::Es256 => Algorithm::ES256,
+            KeyAlgorithm::Rs256 => Algorithm::RS256,
+        }
+    }
+
+    fn encoding_key(self, pem: &[u8]) -> Option<EncodingKey> {
+        match self {
+            KeyAlgorithm::Ed25519 => EncodingKey::from_ed_pem(pem),
+            KeyAlgorithm::Es256 => EncodingKey::from_ec_pem(pem),
+            KeyAlgorithm::Rs256 => EncodingKey::from_rsa_pem(pem),
+        }
+        .ok()
+    }
+
+    fn decoding_key(self, pem: &[u8]) -> Option<DecodingKey> {
+        match self {
+            KeyAlgorithm::Ed25519 => DecodingKey::from_ed_pem(pem),
+            KeyAlgorithm::Es256 => DecodingKey::from_ec_pem(pem),
+            KeyAlgorithm::Rs256 => DecodingKey::from_rsa_pem(pem),
+        }
+        .ok()
+    }
+}
+
+/// A key used to sign and verify stateless tokens, identified by a key id (`kid`).
+///
+/// Besides the signature key, every key carries the pepper mixed into the answer
+/// hash. For HMAC keys the pepper is the shared secret. Asymmetric keys take a
+/// separate pepper, so holders of the public key alone can't brute-force answers.
+#[derive(Clone)]
+pub struct Key {
+    kid: Option<String>,
+    algorithm: Algorithm,
+    encoding: Option<EncodingKey>,
+    decoding: DecodingKey,
+    pepper: Vec<u8>,
+}
+
+impl Key {
+    /// An HS256 key with the given id and shared secret.
+    pub fn hmac(kid: impl Into<String>, secret: impl AsRef<[u8]>) -> Self {
+        Self::from_secret(Some(kid.into()), secret.as_ref())
+    }
+
+    /// A key without id, as used by `Captcha::as_token` and `verify`.
+    pub(crate) fn unnamed(secret: &str) -> Self {
+        Self::from_secret(None, secret.as_bytes())
+    }
+
+    fn from_secret(kid: Option<String>, secret: &[u8]) -> Self {
+        Key {
+            kid,
+            algorithm: Algorithm::HS256,
+            encoding: Some(EncodingKey::from_secret(secret)),
+            decoding: DecodingKey::from_secret(secret),
+            pepper: secret.to_vec(),
+        }
+    }
+
+    /// An asymmetric key able to sign and verify, from PEM-encoded private and
+    /// public keys. Returns `None` if either key can't be parsed.
+    pub fn key_pair(
+        kid: impl Into<String>,
+        algorithm: KeyAlgorithm,
+        private_pem: &[u8],
+        public_pem: &[u8],
+        pepper: impl AsRef<[u8]>,
+    ) -> Option<Self> {
+        let mut key = Self::public_key(kid, algorithm, public_pem, pepper)?;
+        key.encoding = Some(algorithm.encoding_key(private_pem)?);
+        Some(key)
+    }
+
+    /// A verification-only asymmetric key from a PEM-encoded public key.
+    ///
+    /// Verifiers holding only this key can check tokens but never issue them.
+    pub fn public_key(
+        kid: impl Into<String>,
+        algorithm: KeyAlgorithm,
+        public_pem: &[u8],
+        pepper: impl AsRef<[u8]>,
+    ) -> Option<Self> {
+        Some(Key {
+            kid: Some(kid.into()),
+            algorithm: algorithm.jwt_algorithm(),
+            encoding: None,
+            decoding: algorithm.decoding_key(public_pem)?,
+            pepper: pepper.as_ref().to_vec(),
+        })
+    }
+
+    pub fn kid(&self) -> Option<&str> {
+        self.kid.as_deref()
+    }
+
+    /// Whether this key can sign new tokens.
+    pub fn can_sign(&self) -> bool {
+        self.encoding.is_some()
+    }
+
+    pub(crate) fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    pub(crate) fn pepper(&self) -> &[u8] {
+        &self.pepper
+    }
+
+    pub(crate) fn encoding_key(&self) -> Option<&EncodingKey> {
+        self.encoding.as_ref()
+    }
+
+    pub(crate) fn decoding_key(&self) -> &DecodingKey {
+        &self.decoding
+    }
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Key")
+            .field("kid", &self.kid)
+            .field("algorithm", &self.algorithm)
+            .field("can_sign", &self.can_sign())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The current signing key plus retired keys that are still accepted for verification.
+///
+/// Rotating moves the current key to the retired list, so captchas issued before the
+/// rotation keep verifying until they expire. Drop a retired key with
+/// [`KeySet::remove`] once every token signed with it has expired.
+///
+/// ```rust
+/// use captcha_rs::{CaptchaBuilder, Key, KeySet, verify_with_keys};
+///
+/// let mut keys = KeySet::new(Key::hmac("2026-01", "first-secret"));
+///
+/// let captcha = CaptchaBuilder::new().build();
+/// let token = captcha.as_token_with_keys(&keys, 300).unwrap();
+///
+/// keys.rotate(Key::hmac("2026-02", "second-secret"));
+/// assert_eq!(verify_with_keys(&token, &captcha.text, &keys), Some(true));
+/// ```
+#[derive(Debug, Clone)]
+pub struct KeySet {
+    current: Key,
+    retired: Vec<Key>,
+}
+
+impl KeySet {
+    pub fn new(current: Key) -> Self {
+        KeySet {
+            current,
+            retired: Vec::new(),
+        }
+    }
+
+    /// Add a verification-only key.
+    pub fn with_retired(mut self, key: Key) -> Self {
+        self.retired.push(key);
+        self
+    }
+
+    /// Make `next` the signing key and keep the previous one for verification.
+    pub fn rotate(&mut self, next: Key) {
+        let previous = std::mem::replace(&mut self.current, next);
+        self.retired.insert(0, previous);
+    }
+
+    /// Remove a retired key. The current key can't be removed.
+    pub fn remove(&mut self, kid: &str) -> bool {
+        let before = self.retired.len();
+        self.retired.retain(|key| key.kid() != Some(kid));
+        before != self.retired.len()
+    }
+
+    pub fn current(&self) -> &Key {
+        &self.current
+    }
+
+    /// Find the current or a retired key by id.
+    pub fn get(&self, kid: &str) -> Option<&Key> {
+        self.iter().find(|key| key.kid() == Some(kid))
+    }
+
+    /// Iterate over the current key, then the retired keys from newest to oldest.
+    pub fn iter(&self) -> impl Iterator<Item = &Key> {
+        std::iter::once(&self.current).chain(self.retired.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Key, KeySet};
+    use crate::{CaptchaBuilder, verify, verify_with_keys};
+
+    fn captcha() -> crate::Captcha {
+        CaptchaBuilder::new().text(String::from("Rotate")).build()
+    }
+
+    #[test]
+    fn it_writes_the_kid_into_the_header() {
+        let keys = KeySet::new(Key::hmac("k1", "secret-one"));
+        let token = captcha().as_token_with_keys(&keys, 60).unwrap();
+
+        let header = jsonwebtoken::decode_header(&token).unwrap();
+        assert_eq!(header.kid.as_deref(), Some("k1"));
+        assert_eq!(verify_with_keys(&token, "rotate", &keys), Some(true));
+        assert_eq!(verify_with_keys(&token, "wrong", &keys), Some(false));
+    }
+
+    #[test]
+    fn it_keeps_verifying_after_rotation() {
+        let mut keys = KeySet::new(Key::hmac("k1", "secret-one"));
+        let old_token = captcha().as_token_with_keys(&keys, 60).unwrap();
+
+        keys.rotate(Key::hmac("k2", "secret-two"));
+        let new_token = captcha().as_token_with_keys(&keys, 60).unwrap();
+
+        assert_eq!(keys.current().kid(), Some("k2"));
+        assert_eq!(verify_with_keys(&old_token, "rotate", &keys), Some(true));
+        assert_eq!(verify_with_keys(&new_token, "rotate", &keys), Some(true));
+
+        assert!(keys.remove("k1"));
+        assert!(!keys.remove("k2"));
+        assert_eq!(verify_with_keys(&old_token, "rotate", &keys), None);
+    }
+
+    #[test]
+    fn it_rejects_unknown_and_forged_kids() {
+        let keys = KeySet::new(Key::hmac("k1", "secret-one"));
+        let other = KeySet::new(Key::hmac("k1", "attacker-secret"));
+        let forged = captcha().as_token_with_keys(&other, 60).unwrap();
+        assert_eq!(verify_with_keys(&forged, "rotate", &keys), None);
+
+        let unknown = KeySet::new(Key::hmac("k9", "secret-one"));
+        let token = captcha().as_token_with_keys(&unknown, 60).unwrap();
+        assert_eq!(verify_with_keys(&token, "rotate", &keys), None);
+    }
+
+    #[test]
+    fn it_accepts_legacy_tokens_without_kid() {
+        let token = captcha().as_token("secret-one", 60).unwrap();
+        let keys =
+            KeySet::new(Key::hmac("k2", "secret-two")).with_retired(Key::hmac("k1", "secret-one"));
+
+        assert_eq!(verify_with_keys(&token, "rotate", &keys), Some(true));
+        assert_eq!(verify(&token, "rotate", "secret-one"), Some(true));
+    }
+}
@@ -0,0 +1,53 @@
+mod clock;
+#[cfg(feature = "compact")]
+mod compact;
+#[cfg(feature = "stateless")]
+mod context;
+mod error;
+mod hash;
+#[cfg(feature = "stateless")]
+mod jwt;
+#[cfg(feature = "stateless")]
+mod keys;
+#[cfg(feature = "stateless")]
+mod options;
+#[cfg(feature = "stateless")]
+mod pow;
+#[cfg(feature = "stateless")]
+mod replay;
+#[cfg(feature = "encrypted")]
+mod sealed;
+#[cfg(feature = "stateless")]
+mod verifier;
+
+pub use clock::{Clock, DEFAULT_LEEWAY, FixedClock, SystemClock, set_default_clock};
+#[cfg(all(feature = "compact", feature = "stateless"))]
+pub use compact::verify_compact_with;
+#[cfg(feature = "compact")]
+pub use compact::{verify_compact, verify_compact_detailed};
+#[cfg(feature = "stateless")]
+pub use context::TokenContext;
+pub use error::VerifyError;
+#[cfg(feature = "stateless")]
+pub use jwt::{
+    verify, verify_detailed, verify_detailed_with_keys, verify_once, verify_once_with_options,
+    verify_with_context, verify_with_keys, verify_with_options,
+};
+#[cfg(feature = "stateless")]
+pub use keys::{Key, KeyAlgorithm, KeySet};
+#[cfg(feature = "stateless")]
+pub use options::TokenOptions;
+#[cfg(feature = "stateless")]
+pub use pow::{
+    MAX_POW_DIFFICULTY, issue_pow, issue_pow_with, solve_pow, verify_pow, verify_pow_once,
+    verify_pow_with,
+};
+#[cfg(feature = "stateless")]
+pub use replay::{MemoryReplayGuard, ReplayGuard};
+#[cfg(feature = "encrypted")]
+pub use sealed::{
+    SealedChallenge, SealingKey, open_sealed, verify_sealed, verify_sealed_detailed,
+    verify_sealed_once, verify_sealed_with,
+};
+#[cfg(feature = "stateless")]
+pub use verifier::Verifier;
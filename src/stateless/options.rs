@@ -0,0 +1,98 @@
+use std::fmt;
+use std::sync::Arc;
+
+use super::clock::{Clock, DEFAULT_LEEWAY, default_clock};
+
+/// Time settings shared by token issuing and verification.
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use captcha_rs::{
+///     CaptchaBuilder, FixedClock, Key, KeySet, TokenContext, TokenOptions, VerifyError,
+///     verify_with_options,
+/// };
+///
+/// let clock = Arc::new(FixedClock::new(1_000_000));
+/// let options = TokenOptions::new()
+///     .clock(clock.clone())
+///     .leeway(5)
+///     .min_solve_time(2);
+/// let keys = KeySet::new(Key::hmac("2026-01", "your-very-secure-secret"));
+/// let context = TokenContext::default();
+///
+/// let captcha = CaptchaBuilder::new().build();
+/// let token = captcha.as_token_with_options(&keys, 60, &context, &options).unwrap();
+///
+/// // Answered faster than a human could type
+/// let result = verify_with_options(&token, &captcha.text, &keys, &context, &options);
+/// assert_eq!(result, Err(VerifyError::NotYetValid));
+///
+/// clock.advance(3);
+/// let result = verify_with_options(&token, &captcha.text, &keys, &context, &options);
+/// assert_eq!(result, Ok(()));
+/// ```
+#[derive(Clone)]
+pub struct TokenOptions {
+    clock: Arc<dyn Clock>,
+    leeway: u64,
+    min_solve_time: u64,
+}
+
+impl Default for TokenOptions {
+    fn default() -> Self {
+        TokenOptions {
+            clock: default_clock(),
+            leeway: DEFAULT_LEEWAY,
+            min_solve_time: 0,
+        }
+    }
+}
+
+impl TokenOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Time source used to stamp and check tokens.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Seconds a token is still accepted after its expiry, to absorb clock skew
+    /// between the issuing and verifying servers. At most one hour.
+    pub fn leeway(mut self, seconds: u64) -> Self {
+        self.leeway = seconds.min(3600);
+        self
+    }
+
+    /// Seconds after issuing before an answer is accepted, written as the `nbf` claim.
+    ///
+    /// Answers submitted earlier fail with `VerifyError::NotYetValid`. The leeway
+    /// doesn't apply to this check. At most ten minutes.
+    pub fn min_solve_time(mut self, seconds: u64) -> Self {
+        self.min_solve_time = seconds.min(600);
+        self
+    }
+
+    pub(crate) fn now(&self) -> Option<u64> {
+        self.clock.now()
+    }
+
+    pub(crate) fn leeway_seconds(&self) -> u64 {
+        self.leeway
+    }
+
+    pub(crate) fn min_solve_seconds(&self) -> u64 {
+        self.min_solve_time
+    }
+}
+
+impl fmt::Debug for TokenOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenOptions")
+            .field("leeway", &self.leeway)
+            .field("min_solve_time", &self.min_solve_time)
+            .finish_non_exhaustive()
+    }
+}
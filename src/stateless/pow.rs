@@ -0,0 +1,262 @@
+use jsonwebtoken::{DecodingKey, Validation, decode};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::jwt::{decode_with_keys, encode_with_key, mark_used};
+use super::{KeySet, ReplayGuard, TokenOptions, VerifyError};
+use crate::captcha::secure_rng;
+
+/// Highest supported difficulty, in leading zero bits.
+///
+/// Each extra bit doubles the expected work: 20 bits take about a second in a
+/// browser, 32 bits far too long for an interactive flow.
+pub const MAX_POW_DIFFICULTY: u8 = 32;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PowClaims {
+    /// Random seed, so every puzzle needs fresh work.
+    seed: String,
+    /// Required number of leading zero bits.
+    bits: u8,
+    exp: u64,
+    jti: String,
+}
+
+/// Issue a signed proof-of-work puzzle (an invisible captcha) requiring
+/// `difficulty` leading zero bits, clamped to 1..=`MAX_POW_DIFFICULTY`.
+///
+/// The client must find a nonce such that `SHA-256(token || nonce)`, with the nonce
+/// as a big-endian `u64`, starts with that many zero bits, see [`solve_pow`].
+///
+/// ```rust
+/// use captcha_rs::{Key, KeySet, issue_pow, solve_pow, verify_pow};
+///
+/// let keys = KeySet::new(Key::hmac("2026-01", "your-very-secure-secret"));
+/// let token = issue_pow(&keys, 8, 120).unwrap();
+///
+/// // On the client
+/// let nonce = solve_pow(&token).unwrap();
+///
+/// assert_eq!(verify_pow(&token, nonce, &keys), Ok(()));
+/// ```
+pub fn issue_pow(keys: &KeySet, difficulty: u8, expiration_seconds: u64) -> Option<String> {
+    issue_pow_with(
+        keys,
+        difficulty,
+        expiration_seconds,
+        &TokenOptions::default(),
+    )
+}
+
+/// Issue a proof-of-work puzzle whose expiry is stamped with the clock of
+/// `options`. `min_solve_time` doesn't apply to puzzles and is ignored.
+pub fn issue_pow_with(
+    keys: &KeySet,
+    difficulty: u8,
+    expiration_seconds: u64,
+    options: &TokenOptions,
+) -> Option<String> {
+    let seed: [u8; 16] = secure_rng().random();
+    let jti: [u8; 16] = secure_rng().random();
+    let claims = PowClaims {
+        seed: base64::Engine::encode(&base64::engine::general_purpose::URL_SAFE_NO_PAD, seed),
+        bits: difficulty.clamp(1, MAX_POW_DIFFICULTY),
+        exp: options.now()?.checked_add(expiration_seconds)?,
+        jti: base64::Engine::encode(&base64::engine::general_purpose::URL_SAFE_NO_PAD, jti),
+    };
+    encode_with_key(keys.current(), &claims)
+}
+
+/// Find a nonce solving the puzzle, as a client would.
+///
+/// The signature isn't checked, clients don't hold the key. Returns `None` if the
+/// token can't be decoded or asks for more than `MAX_POW_DIFFICULTY` bits.
+pub fn solve_pow(token: &str) -> Option<u64> {
+    let mut validation = Validation::default();
+    validation.insecure_disable_signature_validation();
+    validation.validate_exp = false;
+    validation.required_spec_claims.clear();
+    let claims = decode::<PowClaims>(token, &DecodingKey::from_secret(&[]), &validation)
+        .ok()?
+        .claims;
+    if claims.bits > MAX_POW_DIFFICULTY {
+        return None;
+    }
+    (0..=u64::MAX).find(|&nonce| leading_zero_bits(token, nonce) >= claims.bits as u32)
+}
+
+/// Verify a proof-of-work solution.
+///
+/// Fails with [`VerifyError::WrongAnswer`] if the nonce doesn't meet the difficulty.
+pub fn verify_pow(token: &str, nonce: u64, keys: &KeySet) -> Result<(), VerifyError> {
+    verify_pow_with(token, nonce, keys, &TokenOptions::default(), None)
+}
+
+/// Verify a proof-of-work solution and mark the puzzle as used, so one solution
+/// can't be submitted again.
+pub fn verify_pow_once(
+    token: &str,
+    nonce: u64,
+    keys: &KeySet,
+    guard: &impl ReplayGuard,
+) -> Result<(), VerifyError> {
+    verify_pow_with(token, nonce, keys, &TokenOptions::default(), Some(guard))
+}
+
+/// Verify a proof-of-work solution against the clock and leeway of `options`.
+///
+/// With a `guard`, the puzzle is also marked as used like [`verify_pow_once`].
+pub fn verify_pow_with(
+    token: &str,
+    nonce: u64,
+    keys: &KeySet,
+    options: &TokenOptions,
+    guard: Option<&dyn ReplayGuard>,
+) -> Result<(), VerifyError> {
+    let claims = decode_pow(token, keys, options)?;
+    check_work(token, nonce, &claims)?;
+    if let Some(guard) = guard
+        && !mark_used(guard, &claims.jti, claims.exp, options)?
+    {
+        return Err(VerifyError::Replayed);
+    }
+    Ok(())
+}
+
+fn decode_pow(
+    token: &str,
+    keys: &KeySet,
+    options: &TokenOptions,
+) -> Result<PowClaims, VerifyError> {
+    let (claims, _) = decode_with_keys::<PowClaims>(token, keys)?;
+    let now = options.now().ok_or(VerifyError::ClockUnavailable)?;
+    if claims.exp.saturating_add(options.leeway_seconds()) < now {
+        return Err(VerifyError::Expired);
+    }
+    if claims.bits > MAX_POW_DIFFICULTY {
+        return Err(VerifyError::Malformed);
+    }
+    Ok(claims)
+}
+
+fn check_work(token: &str, nonce: u64, claims: &PowClaims) -> Result<(), VerifyError> {
+    if leading_zero_bits(token, nonce) >= claims.bits as u32 {
+        Ok(())
+    } else {
+        Err(VerifyError::WrongAnswer)
+    }
+}
+
+/// Leading zero bits of `SHA-256(token || nonce)`.
+fn leading_zero_bits(token: &str, nonce: u64) -> u32 {
+    let digest = Sha256::new()
+        .chain_update(token.as_bytes())
+        .chain_update(nonce.to_be_bytes())
+        .finalize();
+    let mut bits = 0;
+    for byte in digest {
+        bits += byte.leading_zeros();
+        if byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stateless::{FixedClock, Key, MemoryReplayGuard};
+    use crate::{CaptchaBuilder, verify_with_keys};
+    use std::sync::Arc;
+
+    fn keys() -> KeySet {
+        KeySet::new(Key::hmac("k1", "pow-secret"))
+    }
+
+    #[test]
+    fn it_solves_and_verifies_a_puzzle() {
+        let keys = keys();
+        let token = issue_pow(&keys, 10, 60).unwrap();
+        let nonce = solve_pow(&token).unwrap();
+
+        assert!(leading_zero_bits(&token, nonce) >= 10);
+        assert_eq!(verify_pow(&token, nonce, &keys), Ok(()));
+
+        // Almost every other nonce misses the target
+        let wrong = (0..).find(|&n| leading_zero_bits(&token, n) < 10).unwrap();
+        assert_eq!(
+            verify_pow(&token, wrong, &keys),
+            Err(VerifyError::WrongAnswer)
+        );
+        assert_eq!(
+            verify_pow(&token, nonce, &KeySet::new(Key::hmac("k1", "other"))),
+            Err(VerifyError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn it_rejects_reused_solutions() {
+        let keys = keys();
+        let guard = MemoryReplayGuard::new();
+        let token = issue_pow(&keys, 4, 60).unwrap();
+        let nonce = solve_pow(&token).unwrap();
+
+        assert_eq!(verify_pow_once(&token, nonce, &keys, &guard), Ok(()));
+        assert_eq!(
+            verify_pow_once(&token, nonce, &keys, &guard),
+            Err(VerifyError::Replayed)
+        );
+    }
+
+    #[test]
+    fn it_verifies_with_the_clock_and_leeway_of_the_options() {
+        let keys = keys();
+        let clock = Arc::new(FixedClock::new(1_000_000));
+        let options = TokenOptions::new().clock(clock.clone()).leeway(10);
+        let guard = MemoryReplayGuard::new();
+        let token = issue_pow_with(&keys, 4, 60, &options).unwrap();
+        let (claims, _) = decode_with_keys::<PowClaims>(&token, &keys).unwrap();
+        assert_eq!(claims.exp, 1_000_060);
+        let nonce = solve_pow(&token).unwrap();
+
+        // The puzzle expired long ago by the system clock
+        assert_eq!(verify_pow(&token, nonce, &keys), Err(VerifyError::Expired));
+
+        clock.advance(70);
+        let verify = |guard: Option<&dyn ReplayGuard>| {
+            verify_pow_with(&token, nonce, &keys, &options, guard)
+        };
+        assert_eq!(verify(None), Ok(()));
+        assert_eq!(verify(Some(&guard)), Ok(()));
+        assert_eq!(verify(Some(&guard)), Err(VerifyError::Replayed));
+
+        clock.advance(1);
+        assert_eq!(verify(None), Err(VerifyError::Expired));
+    }
+
+    #[test]
+    fn puzzles_and_image_tokens_are_not_interchangeable() {
+        let keys = keys();
+        let pow = issue_pow(&keys, 1, 60).unwrap();
+        assert_eq!(verify_with_keys(&pow, "anything", &keys), None);
+
+        let captcha = CaptchaBuilder::new().build();
+        let image = captcha.as_token_with_keys(&keys, 60).unwrap();
+        assert_eq!(verify_pow(&image, 0, &keys), Err(VerifyError::Malformed));
+        assert_eq!(solve_pow(&image), None);
+    }
+
+    #[test]
+    fn it_clamps_the_difficulty() {
+        let keys = keys();
+        let token = issue_pow(&keys, 0, 60).unwrap();
+        let (claims, _) = decode_with_keys::<PowClaims>(&token, &keys).unwrap();
+        assert_eq!(claims.bits, 1);
+
+        let token = issue_pow(&keys, 255, 60).unwrap();
+        let (claims, _) = decode_with_keys::<PowClaims>(&token, &keys).unwrap();
+        assert_eq!(claims.bits, MAX_POW_DIFFICULTY);
+    }
+}
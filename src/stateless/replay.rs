@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Remembers which challenges were already used, so each token verifies only once.
+pub trait ReplayGuard: Send + Sync {
+    /// Mark the challenge `id` as used at `now`, until `expires_at` (seconds since
+    /// the Unix epoch).
+    ///
+    /// `expires_at` already includes the leeway, so it is the last second the token
+    /// is accepted, and `now` is read from the verifier's clock. Returns `false` if
+    /// the challenge was already used.
+    fn mark_used(&self, id: &str, expires_at: u64, now: u64) -> bool;
+}
+
+/// An in-memory [`ReplayGuard`] for a single process.
+///
+/// Entries are dropped once their token has expired. Deployments with several
+/// verifiers need a shared implementation, e.g. backed by Redis `SET NX EX`.
+#[derive(Debug, Default)]
+pub struct MemoryReplayGuard {
+    used: Mutex<HashMap<String, u64>>,
+}
+
+impl MemoryReplayGuard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of challenges currently remembered.
+    pub fn len(&self) -> usize {
+        self.used.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl ReplayGuard for MemoryReplayGuard {
+    fn mark_used(&self, id: &str, expires_at: u64, now: u64) -> bool {
+        let mut used = self.used.lock().unwrap();
+        used.retain(|_, &mut expiry| expiry >= now);
+
+        if used.contains_key(id) {
+            return false;
+        }
+        used.insert(id.to_string(), expires_at);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MemoryReplayGuard, ReplayGuard};
+
+    #[test]
+    fn it_remembers_ids_until_they_expire() {
+        let guard = MemoryReplayGuard::new();
+        assert!(guard.mark_used("a", 160, 100));
+        assert!(!guard.mark_used("a", 160, 160));
+
+        // Pruned only once the token can no longer be accepted
+        assert!(guard.mark_used("b", 300, 161));
+        assert_eq!(guard.len(), 1);
+        assert!(guard.mark_used("a", 160, 161));
+    }
+}
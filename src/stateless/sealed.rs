@@ -0,0 +1,545 @@
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use rand::Rng;
+use std::fmt;
+use subtle::ConstantTimeEq;
+
+use super::clock;
+use super::error::to_option;
+use super::jwt::mark_used;
+use super::{DEFAULT_LEEWAY, ReplayGuard, TokenOptions, VerifyError};
+use crate::captcha::secure_rng;
+use crate::{Captcha, MatchPolicy};
+
+/// Format version written as the first byte of every sealed token.
+const VERSION: u8 = 1;
+/// Associated data binding the ciphertext to this token format.
+const AAD: &[u8] = b"captcha-rs/sealed/v1";
+const NONCE_LEN: usize = 24;
+const ID_LEN: usize = 16;
+
+/// A 256-bit key sealing captcha answers into encrypted tokens.
+#[derive(Clone)]
+pub struct SealingKey {
+    cipher: XChaCha20Poly1305,
+}
+
+impl SealingKey {
+    pub fn new(key: [u8; 32]) -> Self {
+        SealingKey {
+            cipher: XChaCha20Poly1305::new(&key.into()),
+        }
+    }
+
+    /// Build a key from a byte slice, or `None` if it isn't exactly 32 bytes long.
+    pub fn from_slice(key: &[u8]) -> Option<Self> {
+        let key: [u8; 32] = key.try_into().ok()?;
+        Some(Self::new(key))
+    }
+
+    /// Generate a random key.
+    pub fn generate() -> Self {
+        Self::new(secure_rng().random())
+    }
+}
+
+impl fmt::Debug for SealingKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SealingKey").finish_non_exhaustive()
+    }
+}
+
+/// The decrypted contents of a sealed token.
+#[derive(Clone)]
+pub struct SealedChallenge {
+    id: [u8; ID_LEN],
+    expires_at: u64,
+    answer: String,
+    policy: MatchPolicy,
+    context: Vec<u8>,
+}
+
+impl SealedChallenge {
+    /// Random identifier of this challenge, e.g. to reject replays.
+    pub fn id(&self) -> &[u8; ID_LEN] {
+        &self.id
+    }
+
+    /// Expiry as seconds since the Unix epoch.
+    pub fn expires_at(&self) -> u64 {
+        self.expires_at
+    }
+
+    /// Application context sealed alongside the answer.
+    pub fn context(&self) -> &[u8] {
+        &self.context
+    }
+
+    /// The `MatchPolicy` the captcha was issued with.
+    pub fn match_policy(&self) -> &MatchPolicy {
+        &self.policy
+    }
+
+    /// Compare the provided solution to the sealed answer using the sealed policy.
+    ///
+    /// Exact comparisons run in constant time.
+    pub fn matches(&self, provided_solution: &str) -> bool {
+        if self.policy.max_edits() > 0 {
+            return self.policy.matches(&self.answer, provided_solution);
+        }
+        let expected = self.policy.normalize(&self.answer);
+        let provided = self.policy.normalize(provided_solution);
+        expected.as_bytes().ct_eq(provided.as_bytes()).into()
+    }
+}
+
+impl fmt::Debug for SealedChallenge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SealedChallenge")
+            .field("expires_at", &self.expires_at)
+            .field("context", &self.context)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Captcha {
+    /// Encrypt the answer, expiry and `context` into an opaque token.
+    ///
+    /// Unlike `as_token`, nothing about the answer can be learned from the token
+    /// without the key, not even by brute force.
+    pub fn as_sealed_token(
+        &self,
+        key: &SealingKey,
+        expiration_seconds: u64,
+        context: &[u8],
+    ) -> Option<String> {
+        self.as_sealed_token_with(key, expiration_seconds, context, &TokenOptions::default())
+    }
+
+    /// Seal a token whose expiry is stamped with the clock of `options`.
+    ///
+    /// Sealed tokens carry no minimum solve time, so `min_solve_time` is ignored.
+    pub fn as_sealed_token_with(
+        &self,
+        key: &SealingKey,
+        expiration_seconds: u64,
+        context: &[u8],
+        options: &TokenOptions,
+    ) -> Option<String> {
+        let challenge = SealedChallenge {
+            id: secure_rng().random(),
+            expires_at: options.now()?.checked_add(expiration_seconds)?,
+            answer: self.text.clone(),
+            policy: self.match_policy.clone(),
+            context: context.to_vec(),
+        };
+        seal(key, &challenge)
+    }
+
+    pub fn as_sealed_tuple(
+        &self,
+        key: &SealingKey,
+        expiration_seconds: u64,
+        context: &[u8],
+    ) -> Option<(String, String)> {
+        self.as_sealed_token(key, expiration_seconds, context)
+            .map(|token| (self.to_base64(), token))
+    }
+}
+
+/// Encrypt `id || expiry || answer length || answer || policy length || policy || context`
+/// into a token.
+fn seal(key: &SealingKey, challenge: &SealedChallenge) -> Option<String> {
+    let answer = challenge.answer.as_bytes();
+    let answer_len = u8::try_from(answer.len()).ok()?;
+    let policy = challenge.policy.to_bytes();
+    let policy_len = u8::try_from(policy.len()).ok()?;
+
+    let mut plaintext =
+        Vec::with_capacity(ID_LEN + 10 + answer.len() + policy.len() + challenge.context.len());
+    plaintext.extend_from_slice(&challenge.id);
+    plaintext.extend_from_slice(&challenge.expires_at.to_be_bytes());
+    plaintext.push(answer_len);
+    plaintext.extend_from_slice(answer);
+    plaintext.push(policy_len);
+    plaintext.extend_from_slice(&policy);
+    plaintext.extend_from_slice(&challenge.context);
+
+    let nonce: [u8; NONCE_LEN] = secure_rng().random();
+    let ciphertext = key
+        .cipher
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: &plaintext,
+                aad: AAD,
+            },
+        )
+        .ok()?;
+
+    let mut token = Vec::with_capacity(1 + NONCE_LEN + ciphertext.len());
+    token.push(VERSION);
+    token.extend_from_slice(&nonce);
+    token.extend_from_slice(&ciphertext);
+    Some(URL_SAFE_NO_PAD.encode(token))
+}
+
+/// Decrypt a sealed token, returning `None` if it is malformed, tampered with,
+/// sealed with another key or expired.
+pub fn open_sealed(token: &str, key: &SealingKey) -> Option<SealedChallenge> {
+    let challenge = unseal(token, key)?;
+    if challenge.expires_at.saturating_add(DEFAULT_LEEWAY) < clock::now()? {
+        return None;
+    }
+    Some(challenge)
+}
+
+fn unseal(token: &str, key: &SealingKey) -> Option<SealedChallenge> {
+    let bytes = URL_SAFE_NO_PAD.decode(token).ok()?;
+    let (&version, rest) = bytes.split_first()?;
+    if version != VERSION || rest.len() < NONCE_LEN {
+        return None;
+    }
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+    let plaintext = key
+        .cipher
+        .decrypt(
+            XNonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: AAD,
+            },
+        )
+        .ok()?;
+
+    let (id, rest) = plaintext.split_first_chunk::<ID_LEN>()?;
+    let (expires_at, rest) = rest.split_first_chunk::<8>()?;
+    let (&answer_len, rest) = rest.split_first()?;
+    if rest.len() < answer_len as usize {
+        return None;
+    }
+    let (answer, rest) = rest.split_at(answer_len as usize);
+    let (&policy_len, rest) = rest.split_first()?;
+    if rest.len() < policy_len as usize {
+        return None;
+    }
+    let (policy, context) = rest.split_at(policy_len as usize);
+
+    Some(SealedChallenge {
+        id: *id,
+        expires_at: u64::from_be_bytes(*expires_at),
+        answer: String::from_utf8(answer.to_vec()).ok()?,
+        policy: MatchPolicy::from_bytes(policy)?,
+        context: context.to_vec(),
+    })
+}
+
+/// Verify a solution against a sealed token issued with `context`.
+///
+/// Returns `None` if the token can't be opened or was sealed for another context,
+/// otherwise whether the solution matches.
+pub fn verify_sealed(
+    token: &str,
+    provided_solution: &str,
+    key: &SealingKey,
+    context: &[u8],
+) -> Option<bool> {
+    to_option(verify_sealed_detailed(
+        token,
+        provided_solution,
+        key,
+        context,
+    ))
+}
+
+/// Verify a solution against a sealed token issued with `context`, reporting why
+/// it was rejected.
+///
+/// A token that fails to decrypt is reported as [`VerifyError::InvalidSignature`],
+/// since tampering and a wrong key can't be told apart. A token sealed for another
+/// context is reported as [`VerifyError::ContextMismatch`].
+pub fn verify_sealed_detailed(
+    token: &str,
+    provided_solution: &str,
+    key: &SealingKey,
+    context: &[u8],
+) -> Result<(), VerifyError> {
+    verify_sealed_with(
+        token,
+        provided_solution,
+        key,
+        context,
+        &TokenOptions::default(),
+        None,
+    )
+}
+
+/// Verify a sealed token and mark its challenge id as used, so it can't be
+/// submitted again.
+///
+/// Like [`super::verify_once`], every attempt with a genuine token consumes it,
+/// including wrong answers.
+pub fn verify_sealed_once(
+    token: &str,
+    provided_solution: &str,
+    key: &SealingKey,
+    context: &[u8],
+    guard: &impl ReplayGuard,
+) -> Result<(), VerifyError> {
+    verify_sealed_with(
+        token,
+        provided_solution,
+        key,
+        context,
+        &TokenOptions::default(),
+        Some(guard),
+    )
+}
+
+/// Verify a sealed token against `context` and the clock and leeway of `options`.
+///
+/// With a `guard`, the challenge id is also marked as used like
+/// [`verify_sealed_once`], so the token can't be submitted again.
+pub fn verify_sealed_with(
+    token: &str,
+    provided_solution: &str,
+    key: &SealingKey,
+    context: &[u8],
+    options: &TokenOptions,
+    guard: Option<&dyn ReplayGuard>,
+) -> Result<(), VerifyError> {
+    let challenge = open_checked(token, key, context, options)?;
+    if let Some(guard) = guard
+        && !mark_used(
+            guard,
+            &URL_SAFE_NO_PAD.encode(challenge.id),
+            challenge.expires_at,
+            options,
+        )?
+    {
+        return Err(VerifyError::Replayed);
+    }
+    check_answer(&challenge, provided_solution)
+}
+
+/// Open a sealed token and check its expiry and context, but not the answer.
+fn open_checked(
+    token: &str,
+    key: &SealingKey,
+    context: &[u8],
+    options: &TokenOptions,
+) -> Result<SealedChallenge, VerifyError> {
+    let bytes = URL_SAFE_NO_PAD
+        .decode(token)
+        .map_err(|_| VerifyError::Malformed)?;
+    if bytes.first() != Some(&VERSION) || bytes.len() < 1 + NONCE_LEN {
+        return Err(VerifyError::Malformed);
+    }
+    let challenge = unseal(token, key).ok_or(VerifyError::InvalidSignature)?;
+    let now = options.now().ok_or(VerifyError::ClockUnavailable)?;
+    if challenge
+        .expires_at
+        .saturating_add(options.leeway_seconds())
+        < now
+    {
+        return Err(VerifyError::Expired);
+    }
+    if !bool::from(challenge.context.ct_eq(context)) {
+        return Err(VerifyError::ContextMismatch);
+    }
+    Ok(challenge)
+}
+
+fn check_answer(challenge: &SealedChallenge, provided_solution: &str) -> Result<(), VerifyError> {
+    if challenge.matches(provided_solution) {
+        Ok(())
+    } else {
+        Err(VerifyError::WrongAnswer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CaptchaBuilder, FixedClock, MemoryReplayGuard};
+    use std::sync::Arc;
+
+    fn captcha() -> Captcha {
+        CaptchaBuilder::new().text(String::from("SeaLd")).build()
+    }
+
+    #[test]
+    fn it_seals_and_opens_a_challenge() {
+        let key = SealingKey::generate();
+        let token = captcha().as_sealed_token(&key, 60, b"form=signup").unwrap();
+        assert!(!token.contains("SeaLd"));
+
+        let challenge = open_sealed(&token, &key).unwrap();
+        assert_eq!(challenge.context(), b"form=signup");
+        assert!(challenge.matches("seald"));
+        assert!(!challenge.matches("seal"));
+
+        assert_eq!(
+            verify_sealed(&token, "SEALD", &key, b"form=signup"),
+            Some(true)
+        );
+        assert_eq!(
+            verify_sealed(&token, "wrong", &key, b"form=signup"),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn it_uses_a_fresh_nonce_per_token() {
+        let key = SealingKey::generate();
+        let captcha = captcha();
+        let a = captcha.as_sealed_token(&key, 60, b"").unwrap();
+        let b = captcha.as_sealed_token(&key, 60, b"").unwrap();
+        assert_ne!(a, b);
+        assert_ne!(
+            open_sealed(&a, &key).unwrap().id(),
+            open_sealed(&b, &key).unwrap().id()
+        );
+    }
+
+    #[test]
+    fn it_rejects_other_keys_and_tampering() {
+        let key = SealingKey::new([7; 32]);
+        let token = captcha().as_sealed_token(&key, 60, b"ctx").unwrap();
+
+        assert!(open_sealed(&token, &SealingKey::new([8; 32])).is_none());
+
+        let mut bytes = URL_SAFE_NO_PAD.decode(&token).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert!(open_sealed(&URL_SAFE_NO_PAD.encode(bytes), &key).is_none());
+
+        assert!(open_sealed("not-a-token", &key).is_none());
+        assert!(open_sealed("", &key).is_none());
+        assert!(SealingKey::from_slice(&[0; 16]).is_none());
+    }
+
+    #[test]
+    fn it_rejects_expired_tokens() {
+        let key = SealingKey::generate();
+        let expired = SealedChallenge {
+            id: [0; ID_LEN],
+            expires_at: 1,
+            answer: String::from("x"),
+            policy: MatchPolicy::default(),
+            context: Vec::new(),
+        };
+        let token = seal(&key, &expired).unwrap();
+
+        assert!(unseal(&token, &key).is_some());
+        assert!(open_sealed(&token, &key).is_none());
+        assert_eq!(verify_sealed(&token, "x", &key, b""), None);
+        assert_eq!(
+            verify_sealed_detailed(&token, "x", &key, b""),
+            Err(VerifyError::Expired)
+        );
+    }
+
+    #[test]
+    fn it_reports_why_a_sealed_token_was_rejected() {
+        let key = SealingKey::generate();
+        let token = captcha().as_sealed_token(&key, 60, b"").unwrap();
+
+        assert_eq!(verify_sealed_detailed(&token, "seald", &key, b""), Ok(()));
+        assert_eq!(
+            verify_sealed_detailed(&token, "nope", &key, b""),
+            Err(VerifyError::WrongAnswer)
+        );
+        assert_eq!(
+            verify_sealed_detailed(&token, "seald", &SealingKey::generate(), b""),
+            Err(VerifyError::InvalidSignature)
+        );
+        assert_eq!(
+            verify_sealed_detailed("%%", "seald", &key, b""),
+            Err(VerifyError::Malformed)
+        );
+    }
+
+    #[test]
+    fn it_seals_the_match_policy() {
+        let key = SealingKey::generate();
+        let captcha = CaptchaBuilder::new()
+            .text(String::from("SeaLd"))
+            .match_policy(MatchPolicy::new().case_sensitive(true).edit_distance(1))
+            .build();
+        let token = captcha.as_sealed_token(&key, 60, b"").unwrap();
+
+        let challenge = open_sealed(&token, &key).unwrap();
+        assert_eq!(challenge.match_policy(), &captcha.match_policy);
+        assert!(challenge.matches("SeaL"));
+        assert!(!challenge.matches("seald"));
+    }
+
+    #[test]
+    fn it_checks_the_sealed_context() {
+        let key = SealingKey::generate();
+        let token = captcha().as_sealed_token(&key, 60, b"form=signup").unwrap();
+
+        assert_eq!(
+            verify_sealed_detailed(&token, "seald", &key, b"form=signup"),
+            Ok(())
+        );
+        assert_eq!(
+            verify_sealed_detailed(&token, "seald", &key, b"form=login"),
+            Err(VerifyError::ContextMismatch)
+        );
+        assert_eq!(verify_sealed(&token, "seald", &key, b""), None);
+    }
+
+    #[test]
+    fn it_accepts_a_sealed_token_once() {
+        let key = SealingKey::generate();
+        let guard = MemoryReplayGuard::new();
+        let token = captcha().as_sealed_token(&key, 60, b"ctx").unwrap();
+
+        // A context mismatch doesn't consume the token
+        assert_eq!(
+            verify_sealed_once(&token, "seald", &key, b"other", &guard),
+            Err(VerifyError::ContextMismatch)
+        );
+        assert_eq!(
+            verify_sealed_once(&token, "seald", &key, b"ctx", &guard),
+            Ok(())
+        );
+        assert_eq!(
+            verify_sealed_once(&token, "seald", &key, b"ctx", &guard),
+            Err(VerifyError::Replayed)
+        );
+        assert_eq!(guard.len(), 1);
+    }
+
+    #[test]
+    fn it_verifies_with_the_clock_and_leeway_of_the_options() {
+        let key = SealingKey::generate();
+        let clock = Arc::new(FixedClock::new(1_000_000));
+        let options = TokenOptions::new().clock(clock.clone()).leeway(10);
+        let guard = MemoryReplayGuard::new();
+        let token = captcha()
+            .as_sealed_token_with(&key, 60, b"ctx", &options)
+            .unwrap();
+        assert_eq!(unseal(&token, &key).unwrap().expires_at(), 1_000_060);
+
+        clock.advance(70);
+        let verify = |guard: Option<&dyn ReplayGuard>| {
+            verify_sealed_with(&token, "seald", &key, b"ctx", &options, guard)
+        };
+        assert_eq!(verify(None), Ok(()));
+        assert_eq!(verify(Some(&guard)), Ok(()));
+        assert_eq!(verify(Some(&guard)), Err(VerifyError::Replayed));
+        assert_eq!(
+            verify_sealed_with(&token, "seald", &key, b"other", &options, None),
+            Err(VerifyError::ContextMismatch)
+        );
+
+        clock.advance(1);
+        assert_eq!(verify(None), Err(VerifyError::Expired));
+    }
+}
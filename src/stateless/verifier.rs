@@ -0,0 +1,304 @@
+use std::sync::Arc;
+
+use crate::limit::RateLimiter;
+
+use super::jwt::{check_answer, check_context, check_time, decode_with_keys, mark_used};
+use super::replay::ReplayGuard;
+use super::{KeySet, TokenContext, TokenOptions, VerifyError};
+
+/// Verifies stateless tokens with rate limiting and replay protection.
+///
+/// Attempts are counted per client key before the token is even decoded, and per
+/// challenge once its signature checks out. Wrong answers extend the backoff of
+/// both, and a locked out client or challenge fails with
+/// [`VerifyError::LockedOut`]. A correct answer only clears the backoff of its
+/// challenge, a client's failures wear off with time.
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use captcha_rs::{
+///     CaptchaBuilder, FixedClock, Key, KeySet, MemoryRateLimiter, MemoryReplayGuard,
+///     TokenContext, TokenOptions, VerifyError, Verifier,
+/// };
+///
+/// let options = TokenOptions::new().clock(Arc::new(FixedClock::new(1_000_000)));
+/// let keys = KeySet::new(Key::hmac("2026-01", "your-very-secure-secret"));
+/// let verifier = Verifier::new(keys.clone())
+///     .options(options.clone())
+///     .rate_limiter(Arc::new(MemoryRateLimiter::new()))
+///     .replay_guard(Arc::new(MemoryReplayGuard::new()));
+/// let context = TokenContext::default();
+///
+/// let captcha = CaptchaBuilder::new().build();
+/// let token = captcha.as_token_with_options(&keys, 300, &context, &options).unwrap();
+///
+/// let result = verifier.verify(&token, "wrong", &context, Some("203.0.113.7"));
+/// assert_eq!(result, Err(VerifyError::WrongAnswer));
+///
+/// let result = verifier.verify(&token, "wrong", &context, Some("203.0.113.7"));
+/// assert!(matches!(result, Err(VerifyError::LockedOut { .. })));
+/// ```
+#[derive(Clone)]
+pub struct Verifier {
+    keys: KeySet,
+    options: TokenOptions,
+    limiter: Option<Arc<dyn RateLimiter>>,
+    guard: Option<Arc<dyn ReplayGuard>>,
+}
+
+impl Verifier {
+    pub fn new(keys: KeySet) -> Self {
+        Verifier {
+            keys,
+            options: TokenOptions::default(),
+            limiter: None,
+            guard: None,
+        }
+    }
+
+    pub fn options(mut self, options: TokenOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn rate_limiter(mut self, limiter: Arc<dyn RateLimiter>) -> Self {
+        self.limiter = Some(limiter);
+        self
+    }
+
+    /// Reject tokens that were already submitted, see [`super::verify_once`].
+    pub fn replay_guard(mut self, guard: Arc<dyn ReplayGuard>) -> Self {
+        self.guard = Some(guard);
+        self
+    }
+
+    pub fn keys(&self) -> &KeySet {
+        &self.keys
+    }
+
+    /// Verify a solution, counting the attempt against `client` (e.g. an IP address
+    /// or account id) and against the challenge.
+    pub fn verify(
+        &self,
+        token: &str,
+        provided_solution: &str,
+        context: &TokenContext,
+        client: Option<&str>,
+    ) -> Result<(), VerifyError> {
+        let now = self.options.now().ok_or(VerifyError::ClockUnavailable)?;
+        let client_key = client.map(|client| format!("client:{}", client));
+        if let Some(key) = &client_key {
+            self.acquire(key, now)?;
+        }
+
+        let (claims, key) = decode_with_keys(token, &self.keys)?;
+        check_time(&claims, &self.options)?;
+        check_context(&claims, key, context)?;
+
+        // Tokens issued before challenge ids are tracked by their unique hash
+        let challenge_key = format!(
+            "challenge:{}",
+            claims.jti.as_deref().unwrap_or(&claims.hash)
+        );
+        self.acquire(&challenge_key, now)?;
+
+        if let Some(guard) = &self.guard {
+            let id = claims.jti.as_deref().ok_or(VerifyError::Malformed)?;
+            if !mark_used(guard.as_ref(), id, claims.exp as u64, &self.options)? {
+                return Err(VerifyError::Replayed);
+            }
+        }
+
+        let result = check_answer(&claims, key, provided_solution);
+        if let Some(limiter) = &self.limiter {
+            // One solved captcha must not clear the backoff a client built up
+            match result {
+                Ok(()) => limiter.record_success(&challenge_key),
+                Err(_) => {
+                    for key in client_key.iter().chain([&challenge_key]) {
+                        limiter.record_failure(key, now);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    fn acquire(&self, key: &str, now: u64) -> Result<(), VerifyError> {
+        match &self.limiter {
+            Some(limiter) => limiter
+                .acquire(key, now)
+                .map_err(|retry_after| VerifyError::LockedOut { retry_after }),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::fmt::Debug for Verifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Verifier")
+            .field("keys", &self.keys)
+            .field("options", &self.options)
+            .field("rate_limited", &self.limiter.is_some())
+            .field("replay_guarded", &self.guard.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryRateLimiter;
+    use crate::stateless::{FixedClock, Key, MemoryReplayGuard};
+    use crate::{Captcha, CaptchaBuilder};
+
+    fn captcha() -> Captcha {
+        CaptchaBuilder::new().text(String::from("LiMit")).build()
+    }
+
+    fn keys() -> KeySet {
+        KeySet::new(Key::hmac("k1", "limit-secret"))
+    }
+
+    #[test]
+    fn it_locks_out_a_challenge_after_failures() {
+        let clock = Arc::new(FixedClock::new(1_000_000));
+        let verifier = Verifier::new(keys())
+            .options(TokenOptions::new().clock(clock.clone()))
+            .rate_limiter(Arc::new(MemoryRateLimiter::new().backoff(4, 60)));
+        let context = TokenContext::default();
+        let token = captcha()
+            .as_token_with_options(
+                verifier.keys(),
+                300,
+                &context,
+                &TokenOptions::new().clock(clock.clone()),
+            )
+            .unwrap();
+
+        assert_eq!(
+            verifier.verify(&token, "wrong", &context, None),
+            Err(VerifyError::WrongAnswer)
+        );
+        assert_eq!(
+            verifier.verify(&token, "limit", &context, None),
+            Err(VerifyError::LockedOut { retry_after: 4 })
+        );
+        clock.advance(4);
+        assert_eq!(verifier.verify(&token, "limit", &context, None), Ok(()));
+    }
+
+    #[test]
+    fn it_limits_each_client_across_challenges() {
+        let clock = Arc::new(FixedClock::new(1_000_000));
+        let options = TokenOptions::new().clock(clock.clone());
+        let verifier = Verifier::new(keys())
+            .options(options.clone())
+            .rate_limiter(Arc::new(MemoryRateLimiter::new().capacity(2)));
+        let context = TokenContext::default();
+        let token = || {
+            captcha()
+                .as_token_with_options(verifier.keys(), 300, &context, &options)
+                .unwrap()
+        };
+
+        let client = Some("203.0.113.7");
+        assert_eq!(verifier.verify(&token(), "limit", &context, client), Ok(()));
+        assert_eq!(verifier.verify(&token(), "limit", &context, client), Ok(()));
+        assert_eq!(
+            verifier.verify(&token(), "limit", &context, client),
+            Err(VerifyError::LockedOut { retry_after: 60 })
+        );
+        // Even garbage counts against the client
+        assert_eq!(
+            verifier.verify("garbage", "limit", &context, client),
+            Err(VerifyError::LockedOut { retry_after: 60 })
+        );
+        assert_eq!(
+            verifier.verify(&token(), "limit", &context, Some("198.51.100.1")),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn it_rejects_replays() {
+        let verifier = Verifier::new(keys()).replay_guard(Arc::new(MemoryReplayGuard::new()));
+        let context = TokenContext::default();
+        let token = captcha().as_token_with_keys(verifier.keys(), 300).unwrap();
+
+        assert_eq!(verifier.verify(&token, "limit", &context, None), Ok(()));
+        assert_eq!(
+            verifier.verify(&token, "limit", &context, None),
+            Err(VerifyError::Replayed)
+        );
+    }
+
+    #[test]
+    fn it_rejects_replays_until_the_leeway_ends() {
+        let clock = Arc::new(FixedClock::new(1_000_000));
+        let options = TokenOptions::new().clock(clock.clone()).leeway(30);
+        let verifier = Verifier::new(keys())
+            .options(options.clone())
+            .replay_guard(Arc::new(MemoryReplayGuard::new()));
+        let context = TokenContext::default();
+        let token = captcha()
+            .as_token_with_options(verifier.keys(), 60, &context, &options)
+            .unwrap();
+
+        // Past `exp` but within the leeway, the token is accepted once
+        clock.advance(80);
+        assert_eq!(verifier.verify(&token, "limit", &context, None), Ok(()));
+        clock.advance(10);
+        assert_eq!(
+            verifier.verify(&token, "limit", &context, None),
+            Err(VerifyError::Replayed)
+        );
+        clock.advance(1);
+        assert_eq!(
+            verifier.verify(&token, "limit", &context, None),
+            Err(VerifyError::Expired)
+        );
+    }
+
+    #[test]
+    fn it_keeps_the_client_backoff_after_a_correct_answer() {
+        let clock = Arc::new(FixedClock::new(1_000_000));
+        let options = TokenOptions::new().clock(clock.clone());
+        let verifier = Verifier::new(keys())
+            .options(options.clone())
+            .rate_limiter(Arc::new(MemoryRateLimiter::new().backoff(4, 60)));
+        let context = TokenContext::default();
+        let token = || {
+            captcha()
+                .as_token_with_options(verifier.keys(), 300, &context, &options)
+                .unwrap()
+        };
+
+        let client = Some("203.0.113.7");
+        for (wait, expected) in [(0, 4), (4, 8)] {
+            clock.advance(wait);
+            assert_eq!(
+                verifier.verify(&token(), "wrong", &context, client),
+                Err(VerifyError::WrongAnswer)
+            );
+            assert_eq!(
+                verifier.verify(&token(), "limit", &context, client),
+                Err(VerifyError::LockedOut {
+                    retry_after: expected
+                })
+            );
+        }
+        clock.advance(8);
+        assert_eq!(verifier.verify(&token(), "limit", &context, client), Ok(()));
+
+        // The lucky solve did not reset the client, the next failure doubles again
+        assert_eq!(
+            verifier.verify(&token(), "wrong", &context, client),
+            Err(VerifyError::WrongAnswer)
+        );
+        assert_eq!(
+            verifier.verify(&token(), "limit", &context, client),
+            Err(VerifyError::LockedOut { retry_after: 16 })
+        );
+    }
+}
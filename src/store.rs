@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use rand::Rng;
+
+use crate::captcha::secure_rng;
+use crate::{Captcha, MatchPolicy};
+
+/// The answer of a captcha kept server-side, as an alternative to stateless tokens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredCaptcha {
+    pub text: String,
+    pub match_policy: MatchPolicy,
+}
+
+impl StoredCaptcha {
+    /// Compare a provided answer using the captcha's `MatchPolicy`.
+    pub fn matches(&self, provided_solution: &str) -> bool {
+        self.match_policy.matches(&self.text, provided_solution)
+    }
+}
+
+impl From<&Captcha> for StoredCaptcha {
+    fn from(captcha: &Captcha) -> Self {
+        StoredCaptcha {
+            text: captcha.text.clone(),
+            match_policy: captcha.match_policy.clone(),
+        }
+    }
+}
+
+/// Keeps captcha answers by id until they are verified or expire.
+pub trait CaptchaStore: Send + Sync {
+    /// Remember the answer of `captcha` for `ttl` and return the id to send to the client.
+    fn insert(&self, captcha: &Captcha, ttl: Duration) -> String;
+
+    /// Remove and return the answer for `id`, or `None` if unknown or expired.
+    fn take(&self, id: &str) -> Option<StoredCaptcha>;
+
+    /// Check an answer. The captcha is consumed either way, so every id verifies once.
+    fn verify(&self, id: &str, provided_solution: &str) -> bool {
+        self.take(id)
+            .is_some_and(|captcha| captcha.matches(provided_solution))
+    }
+}
+
+/// An in-memory [`CaptchaStore`] for a single process.
+///
+/// Holds at most `capacity` captchas. When full, expired entries are dropped first,
+/// then the ones closest to expiry.
+#[derive(Debug)]
+pub struct MemoryStore {
+    entries: Mutex<HashMap<String, (StoredCaptcha, Instant)>>,
+    capacity: usize,
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        MemoryStore {
+            entries: Mutex::new(HashMap::new()),
+            capacity: 10_000,
+        }
+    }
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maximum number of captchas kept, at least 1.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Number of captchas currently stored.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl CaptchaStore for MemoryStore {
+    fn insert(&self, captcha: &Captcha, ttl: Duration) -> String {
+        let id = URL_SAFE_NO_PAD.encode(secure_rng().random::<[u8; 16]>());
+        let now = Instant::now();
+        let expires_at = now.checked_add(ttl).unwrap_or(now);
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.capacity {
+            entries.retain(|_, (_, expiry)| *expiry > now);
+        }
+        while entries.len() >= self.capacity {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, (_, expiry))| *expiry)
+                .map(|(id, _)| id.clone());
+            match oldest {
+                Some(oldest) => entries.remove(&oldest),
+                None => break,
+            };
+        }
+        entries.insert(id.clone(), (captcha.into(), expires_at));
+        id
+    }
+
+    fn take(&self, id: &str) -> Option<StoredCaptcha> {
+        let (captcha, expires_at) = self.entries.lock().unwrap().remove(id)?;
+        (Instant::now() < expires_at).then_some(captcha)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CaptchaStore, MemoryStore};
+    use crate::CaptchaBuilder;
+    use std::time::Duration;
+
+    #[test]
+    fn it_verifies_each_id_once() {
+        let store = MemoryStore::new();
+        let captcha = CaptchaBuilder::new().text(String::from("Store")).build();
+        let id = store.insert(&captcha, Duration::from_secs(60));
+
+        assert_eq!(store.len(), 1);
+        assert!(!store.verify("unknown", "store"));
+        assert!(store.verify(&id, "store"));
+        assert!(!store.verify(&id, "store"));
+        assert!(store.is_empty());
+
+        let id = store.insert(&captcha, Duration::from_secs(60));
+        assert!(!store.verify(&id, "wrong"));
+        assert!(!store.verify(&id, "store"));
+    }
+
+    #[test]
+    fn it_drops_expired_and_oldest_entries() {
+        let store = MemoryStore::new().capacity(2);
+        let captcha = CaptchaBuilder::new().text(String::from("full")).build();
+
+        let expired = store.insert(&captcha, Duration::ZERO);
+        assert!(store.take(&expired).is_none());
+
+        let first = store.insert(&captcha, Duration::from_secs(10));
+        let second = store.insert(&captcha, Duration::from_secs(60));
+        let third = store.insert(&captcha, Duration::from_secs(60));
+
+        assert_eq!(store.len(), 2);
+        assert!(store.take(&first).is_none());
+        assert!(store.verify(&second, "FULL"));
+        assert!(store.verify(&third, "full"));
+    }
+}
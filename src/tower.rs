@@ -0,0 +1,596 @@
+//! Framework-agnostic [tower](https://docs.rs/tower) middleware requiring a solved
+//! captcha on protected routes.
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+use http::header::{self, HeaderName, HeaderValue};
+use http::request::Parts;
+use http::{Request, Response, StatusCode};
+use http_body::Body;
+use http_body_util::{BodyExt, Limited};
+use serde::Serialize;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::limit::{MemoryRateLimiter, RateLimiter};
+use crate::web::{CaptchaRejection, CaptchaService, ChallengeBody};
+
+/// Wraps a service so protected requests must carry a valid captcha token and answer.
+///
+/// The token and answer are read from the `X-Captcha-Token` and `X-Captcha-Answer`
+/// headers, or from the `captcha_token` and `captcha_answer` fields of a form body.
+/// Both names are configurable. Requests without a valid captcha get a JSON error
+/// with a fresh challenge, a 400 if the captcha is missing and a 403 if it is wrong:
+///
+/// ```json
+/// {"error": "captcha verification failed", "challenge": {"token": "...", "image": "data:image/jpeg;base64,...", "expires_in": 300}}
+/// ```
+///
+/// Rendering a challenge costs far more than rejecting a request, so challenges are
+/// rate limited per client, by default to a burst of 20 and one more every 3
+/// seconds. Past the limit, requests get a 429 without a challenge.
+///
+/// Attempts are rate limited by the key returned from [`CaptchaLayer::client_key`],
+/// by default the peer address from a `SocketAddr` request extension or, with the
+/// `axum` feature, from axum's `ConnectInfo<SocketAddr>`.
+///
+/// Form bodies are buffered and handed to the inner service unchanged, so request
+/// bodies must implement `From<Bytes>`, as do axum's `Body` and `Full<Bytes>`.
+///
+/// ```rust
+/// use captcha_rs::tower::CaptchaLayer;
+/// use captcha_rs::{CaptchaBuilder, CaptchaService, MemoryStore};
+/// use std::sync::Arc;
+///
+/// let service = CaptchaService::with_store(CaptchaBuilder::new(), Arc::new(MemoryStore::new()));
+/// let layer = CaptchaLayer::new(service)
+///     .protect("/signup")
+///     .protect("/password-reset")
+///     .answer_field("captcha");
+/// ```
+#[derive(Clone)]
+pub struct CaptchaLayer {
+    config: Arc<Config>,
+}
+
+#[derive(Clone)]
+struct Config {
+    service: CaptchaService,
+    paths: Vec<Vec<String>>,
+    token_header: HeaderName,
+    answer_header: HeaderName,
+    token_field: String,
+    answer_field: String,
+    max_body: usize,
+    client_key: ClientKey,
+    challenge_limiter: Arc<dyn RateLimiter>,
+}
+
+type ClientKey = Arc<dyn Fn(&Parts) -> Option<String> + Send + Sync>;
+
+impl CaptchaLayer {
+    pub fn new(service: CaptchaService) -> Self {
+        CaptchaLayer {
+            config: Arc::new(Config {
+                service,
+                paths: Vec::new(),
+                token_header: HeaderName::from_static("x-captcha-token"),
+                answer_header: HeaderName::from_static("x-captcha-answer"),
+                token_field: String::from("captcha_token"),
+                answer_field: String::from("captcha_answer"),
+                max_body: 64 * 1024,
+                client_key: Arc::new(peer_address),
+                challenge_limiter: Arc::new(
+                    MemoryRateLimiter::new().capacity(20).refill_interval(3),
+                ),
+            }),
+        }
+    }
+
+    /// The configuration of this layer, copied first if a clone shares it.
+    fn config(&mut self) -> &mut Config {
+        Arc::make_mut(&mut self.config)
+    }
+
+    /// Protect requests whose path starts with the segments of `prefix`, so
+    /// `/signup` covers `/signup/confirm` but not `/signup-other`. Paths are compared
+    /// after removing empty and `.` segments and resolving `..`. Without any prefix
+    /// every request is protected.
+    pub fn protect(mut self, prefix: impl Into<String>) -> Self {
+        let prefix = segments(&prefix.into())
+            .into_iter()
+            .map(String::from)
+            .collect();
+        self.config().paths.push(prefix);
+        self
+    }
+
+    /// Headers carrying the token and answer.
+    pub fn headers(mut self, token: HeaderName, answer: HeaderName) -> Self {
+        let config = self.config();
+        config.token_header = token;
+        config.answer_header = answer;
+        self
+    }
+
+    /// Form field carrying the token.
+    pub fn token_field(mut self, name: impl Into<String>) -> Self {
+        self.config().token_field = name.into();
+        self
+    }
+
+    /// Form field carrying the answer.
+    pub fn answer_field(mut self, name: impl Into<String>) -> Self {
+        self.config().answer_field = name.into();
+        self
+    }
+
+    /// Largest form body buffered to read the captcha fields, at least 1 KiB.
+    pub fn max_body(mut self, bytes: usize) -> Self {
+        self.config().max_body = bytes.max(1024);
+        self
+    }
+
+    /// Key counting attempts against the rate limiter of a [`crate::Verifier`]
+    /// backend, e.g. a client IP set by a proxy or an account id.
+    pub fn client_key<F>(mut self, key: F) -> Self
+    where
+        F: Fn(&Parts) -> Option<String> + Send + Sync + 'static,
+    {
+        self.config().client_key = Arc::new(key);
+        self
+    }
+
+    /// Limits the challenges rendered for rejected requests, per client key.
+    /// Requests without a client key share one bucket.
+    pub fn challenge_limiter(mut self, limiter: Arc<dyn RateLimiter>) -> Self {
+        self.config().challenge_limiter = limiter;
+        self
+    }
+}
+
+/// The segments of a path, without empty and `.` segments and with `..` resolved.
+fn segments(path: &str) -> Vec<&str> {
+    let mut segments = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    segments
+}
+
+/// The peer IP address, from axum's `ConnectInfo` or a plain `SocketAddr` extension.
+fn peer_address(parts: &Parts) -> Option<String> {
+    #[cfg(feature = "axum")]
+    if let Some(axum::extract::ConnectInfo(addr)) = parts
+        .extensions
+        .get::<axum::extract::ConnectInfo<SocketAddr>>()
+    {
+        return Some(addr.ip().to_string());
+    }
+    parts
+        .extensions
+        .get::<SocketAddr>()
+        .map(|addr| addr.ip().to_string())
+}
+
+impl<S> Layer<S> for CaptchaLayer {
+    type Service = CaptchaMiddleware<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CaptchaMiddleware {
+            inner,
+            config: Arc::clone(&self.config),
+        }
+    }
+}
+
+/// The service created by [`CaptchaLayer`].
+#[derive(Clone)]
+pub struct CaptchaMiddleware<S> {
+    inner: S,
+    config: Arc<Config>,
+}
+
+#[derive(Serialize)]
+struct ChallengeError {
+    error: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    challenge: Option<ChallengeBody>,
+}
+
+impl Config {
+    fn protects(&self, path: &str) -> bool {
+        if self.paths.is_empty() {
+            return true;
+        }
+        let path = segments(path);
+        self.paths.iter().any(|prefix| {
+            prefix.len() <= path.len() && prefix.iter().zip(&path).all(|(a, b)| a == b)
+        })
+    }
+
+    fn header(&self, headers: &http::HeaderMap, name: &HeaderName) -> Option<String> {
+        let value = headers.get(name)?.to_str().ok()?;
+        Some(value.to_string())
+    }
+
+    /// Read the token and answer from the headers, or else from a form body.
+    ///
+    /// Returns the request with its body restored.
+    async fn credentials<B>(
+        &self,
+        request: Request<B>,
+    ) -> Result<(Request<B>, Option<(String, String)>), CaptchaRejection>
+    where
+        B: Body + From<Bytes>,
+        B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let headers = request.headers();
+        if let (Some(token), Some(answer)) = (
+            self.header(headers, &self.token_header),
+            self.header(headers, &self.answer_header),
+        ) {
+            return Ok((request, Some((token, answer))));
+        }
+
+        let form = headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("application/x-www-form-urlencoded"));
+        if !form {
+            return Ok((request, None));
+        }
+
+        let (parts, body) = request.into_parts();
+        let bytes = Limited::new(body, self.max_body)
+            .collect()
+            .await
+            .map_err(|_| CaptchaRejection::InvalidBody)?
+            .to_bytes();
+        let fields: Vec<(String, String)> =
+            serde_urlencoded::from_bytes(&bytes).map_err(|_| CaptchaRejection::InvalidBody)?;
+
+        let field = |name: &str| {
+            fields
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+        };
+        let credentials = field(&self.token_field).zip(field(&self.answer_field));
+        Ok((Request::from_parts(parts, B::from(bytes)), credentials))
+    }
+
+    /// A JSON error response with a fresh captcha to solve, or a 429 without one if
+    /// the client asked for too many.
+    fn reject<B: From<Bytes>>(
+        &self,
+        rejection: CaptchaRejection,
+        client: Option<&str>,
+    ) -> Response<B> {
+        let key = format!("challenge:{}", client.unwrap_or_default());
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        let (rejection, challenge, retry_after) = match self.challenge_limiter.acquire(&key, now) {
+            Ok(()) => {
+                let challenge = self
+                    .service
+                    .issue()
+                    .map(|challenge| challenge.body(self.service.expires_in()));
+                (rejection, challenge, None)
+            }
+            Err(retry_after) => (CaptchaRejection::RateLimited, None, Some(retry_after)),
+        };
+        let body = ChallengeError {
+            error: rejection.to_string(),
+            challenge,
+        };
+        let body = serde_json::to_vec(&body).unwrap_or_default();
+
+        let mut response = Response::new(B::from(Bytes::from(body)));
+        *response.status_mut() =
+            StatusCode::from_u16(rejection.status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let headers = response.headers_mut();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+        headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        if let Some(retry_after) = retry_after {
+            headers.insert(header::RETRY_AFTER, HeaderValue::from(retry_after.max(1)));
+        }
+        response
+    }
+}
+
+impl<S, B, ResBody> Service<Request<B>> for CaptchaMiddleware<S>
+where
+    S: Service<Request<B>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    B: Body + From<Bytes> + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    ResBody: From<Bytes>,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        if !self.config.protects(request.uri().path()) {
+            return Box::pin(self.inner.call(request));
+        }
+
+        // The clone may not be ready, so keep the service polled by `poll_ready`
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let config = Arc::clone(&self.config);
+
+        let (parts, body) = request.into_parts();
+        let client = (config.client_key)(&parts);
+        let request = Request::from_parts(parts, body);
+
+        Box::pin(async move {
+            let (request, credentials) = match config.credentials(request).await {
+                Ok(result) => result,
+                Err(rejection) => return Ok(config.reject(rejection, client.as_deref())),
+            };
+            match credentials {
+                Some((token, answer))
+                    if config.service.verify(&token, &answer, client.as_deref()) =>
+                {
+                    inner.call(request).await
+                }
+                Some(_) => Ok(config.reject(CaptchaRejection::Failed, client.as_deref())),
+                None => Ok(config.reject(CaptchaRejection::Missing, client.as_deref())),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CaptchaLayer;
+    use crate::{CaptchaBuilder, CaptchaService, MemoryStore};
+    use bytes::Bytes;
+    use http::{Request, Response, StatusCode, header};
+    use http_body_util::{BodyExt, Full};
+    use std::convert::Infallible;
+    use std::sync::Arc;
+    use tower::{Service, ServiceBuilder, ServiceExt};
+
+    type Body = Full<Bytes>;
+
+    fn service() -> CaptchaService {
+        let builder = CaptchaBuilder::new().text(String::from("tower"));
+        CaptchaService::with_store(builder, Arc::new(MemoryStore::new()))
+    }
+
+    /// Echoes the request body, to check it reaches the inner service intact.
+    async fn echo(request: Request<Body>) -> Result<Response<Body>, Infallible> {
+        let body = request.into_body().collect().await.unwrap().to_bytes();
+        Ok(Response::new(Full::new(body)))
+    }
+
+    async fn body(response: Response<Body>) -> serde_json::Value {
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice(&bytes).unwrap_or_default()
+    }
+
+    #[tokio::test]
+    async fn it_challenges_and_accepts_requests() {
+        let captchas = service();
+        let mut app = ServiceBuilder::new()
+            .layer(CaptchaLayer::new(captchas.clone()).protect("/signup"))
+            .service_fn(echo);
+
+        let request = Request::post("/signup").body(Body::default()).unwrap();
+        let response = app.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-store");
+        let json = body(response).await;
+        assert_eq!(json["error"], "missing captcha");
+        let token = json["challenge"]["token"].as_str().unwrap().to_string();
+        assert!(
+            json["challenge"]["image"]
+                .as_str()
+                .unwrap()
+                .starts_with("data:image/")
+        );
+
+        let form = format!(
+            "email=a%40example.com&captcha_token={}&captcha_answer=TOWER",
+            token
+        );
+        let request = Request::post("/signup")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from(form.clone()))
+            .unwrap();
+        let response = app.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let echoed = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(echoed, form.as_bytes());
+
+        // Tokens are accepted once
+        let request = Request::post("/signup")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from(form))
+            .unwrap();
+        let response = app.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(body(response).await["challenge"]["token"].is_string());
+
+        let request = Request::get("/public").body(Body::default()).unwrap();
+        let response = app.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn it_matches_whole_normalized_path_segments() {
+        let app = ServiceBuilder::new()
+            .layer(CaptchaLayer::new(service()).protect("/signup/"))
+            .service_fn(echo);
+
+        for path in [
+            "/signup",
+            "//signup",
+            "/./signup",
+            "/public/../signup",
+            "/signup/confirm",
+        ] {
+            let request = Request::post(path).body(Body::default()).unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", path);
+        }
+        for path in ["/signup-other", "/", "/public/signup"] {
+            let request = Request::post(path).body(Body::default()).unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK, "{}", path);
+        }
+    }
+
+    #[tokio::test]
+    async fn it_rate_limits_challenges_per_client() {
+        use crate::MemoryRateLimiter;
+        use std::net::SocketAddr;
+
+        let limiter = MemoryRateLimiter::new().capacity(2).refill_interval(3600);
+        let layer = CaptchaLayer::new(service()).challenge_limiter(Arc::new(limiter));
+        let app = ServiceBuilder::new().layer(layer).service_fn(echo);
+        let request = |peer: &str| {
+            let mut request = Request::post("/signup").body(Body::default()).unwrap();
+            let addr: SocketAddr = peer.parse().unwrap();
+            request.extensions_mut().insert(addr);
+            request
+        };
+
+        for _ in 0..2 {
+            let response = app.clone().oneshot(request("203.0.113.7:1")).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            assert!(body(response).await["challenge"]["token"].is_string());
+        }
+        let response = app.clone().oneshot(request("203.0.113.7:2")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "3600");
+        let json = body(response).await;
+        assert_eq!(json["error"], "too many captcha requests");
+        assert!(json.get("challenge").is_none());
+
+        let response = app.oneshot(request("198.51.100.2:1")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn it_reads_configurable_headers() {
+        let captchas = service();
+        let token = captchas.issue().unwrap().token;
+        let layer = CaptchaLayer::new(captchas).headers(
+            header::HeaderName::from_static("x-token"),
+            header::HeaderName::from_static("x-answer"),
+        );
+        let app = ServiceBuilder::new().layer(layer).service_fn(echo);
+
+        let request = Request::post("/anything")
+            .header("x-token", &token)
+            .header("x-answer", "tower")
+            .body(Body::from("payload"))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let request = Request::post("/anything")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from(vec![b'a'; 128 * 1024]))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[cfg(feature = "stateless")]
+    #[tokio::test]
+    async fn it_rate_limits_by_client_key() {
+        use crate::{Key, KeySet, MemoryRateLimiter, Verifier};
+        use std::net::SocketAddr;
+
+        let verifier = Verifier::new(KeySet::new(Key::unnamed("tower-secret")))
+            .rate_limiter(Arc::new(MemoryRateLimiter::new().backoff(60, 60)));
+        let builder = CaptchaBuilder::new().text(String::from("tower"));
+        let captchas = CaptchaService::with_verifier(builder, verifier);
+
+        let request = |answer: &str, peer: &str, user: &str| {
+            let mut request = Request::post("/signup")
+                .header("x-captcha-token", captchas.issue().unwrap().token)
+                .header("x-captcha-answer", answer)
+                .header("x-user", user)
+                .body(Body::default())
+                .unwrap();
+            let addr: SocketAddr = peer.parse().unwrap();
+            request.extensions_mut().insert(addr);
+            request
+        };
+
+        // The peer address is the default key
+        let app = ServiceBuilder::new()
+            .layer(CaptchaLayer::new(captchas.clone()))
+            .service_fn(echo);
+        let status = |response: Response<Body>| response.status();
+        let response = app.clone().oneshot(request("nope", "203.0.113.7:1", "a"));
+        assert_eq!(status(response.await.unwrap()), StatusCode::FORBIDDEN);
+        let response = app.clone().oneshot(request("tower", "203.0.113.7:2", "a"));
+        assert_eq!(status(response.await.unwrap()), StatusCode::FORBIDDEN);
+        let response = app.oneshot(request("tower", "198.51.100.2:1", "a"));
+        assert_eq!(status(response.await.unwrap()), StatusCode::OK);
+
+        let layer = CaptchaLayer::new(captchas.clone()).client_key(|parts| {
+            let user = parts.headers.get("x-user")?.to_str().ok()?;
+            Some(user.to_string())
+        });
+        let app = ServiceBuilder::new().layer(layer).service_fn(echo);
+        let response = app.clone().oneshot(request("nope", "192.0.2.1:1", "b"));
+        assert_eq!(status(response.await.unwrap()), StatusCode::FORBIDDEN);
+        let response = app.clone().oneshot(request("tower", "192.0.2.2:1", "b"));
+        assert_eq!(status(response.await.unwrap()), StatusCode::FORBIDDEN);
+        let response = app.oneshot(request("tower", "192.0.2.1:1", "c"));
+        assert_eq!(status(response.await.unwrap()), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn it_configures_cloned_layers_independently() {
+        let captchas = service();
+        let layer = CaptchaLayer::new(captchas.clone());
+        let shared = layer.clone();
+        let app = ServiceBuilder::new()
+            .layer(layer.protect("/signup"))
+            .service_fn(echo);
+
+        let request = Request::get("/public").body(Body::default()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let request = Request::get("/signup").body(Body::default()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // The clone still protects every path
+        let app = ServiceBuilder::new().layer(shared).service_fn(echo);
+        let request = Request::get("/public").body(Body::default()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
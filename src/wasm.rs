@@ -0,0 +1,97 @@
+//! [wasm-bindgen](https://docs.rs/wasm-bindgen) bindings for browsers and WebAssembly
+//! edge runtimes.
+//!
+//! ```js
+//! import { generate } from "captcha-rs";
+//!
+//! const captcha = generate(JSON.stringify({ length: 6, secret: "change-me" }));
+//! img.src = captcha.image;
+//! form.token.value = captcha.token;
+//! ```
+
+use serde::Deserialize;
+use wasm_bindgen::prelude::*;
+
+use crate::CaptchaConfig;
+
+/// Settings of [`generate`]: every [`CaptchaConfig`] field, plus the secret signing
+/// the token and its lifetime in seconds.
+#[derive(Deserialize)]
+#[serde(default)]
+struct GenerateConfig {
+    #[serde(flatten)]
+    captcha: CaptchaConfig,
+    secret: Option<String>,
+    expires: u64,
+}
+
+impl Default for GenerateConfig {
+    fn default() -> Self {
+        GenerateConfig {
+            captcha: CaptchaConfig::default(),
+            secret: None,
+            expires: 300,
+        }
+    }
+}
+
+/// A generated captcha: the image as a data URI and, if a secret was given, a
+/// stateless token verifiable with [`crate::verify`].
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Debug, Clone)]
+pub struct GeneratedCaptcha {
+    pub image: String,
+    pub token: Option<String>,
+}
+
+/// Generate a captcha from a JSON [`CaptchaConfig`], which may also set `secret`
+/// and `expires` (default 300 seconds) to sign a token.
+///
+/// Throws if the JSON is invalid. Font paths can't be loaded on the web, so `fonts`
+/// must be empty there.
+#[wasm_bindgen]
+pub fn generate(config_json: &str) -> Result<GeneratedCaptcha, JsError> {
+    render(config_json).map_err(|error| JsError::new(&error))
+}
+
+fn render(config_json: &str) -> Result<GeneratedCaptcha, String> {
+    let config: GenerateConfig = serde_json::from_str(config_json)
+        .map_err(|error| format!("invalid configuration: {}", error))?;
+    let builder = config
+        .captcha
+        .builder()
+        .map_err(|error| format!("invalid configuration: {}", error))?;
+
+    let captcha = builder.build();
+    let token = match &config.secret {
+        Some(secret) => Some(
+            captcha
+                .as_token(secret, config.expires)
+                .ok_or_else(|| String::from("failed to sign token"))?,
+        ),
+        None => None,
+    };
+    Ok(GeneratedCaptcha {
+        image: captcha.to_base64(),
+        token,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render;
+
+    #[test]
+    fn it_generates_from_json() {
+        let captcha = render(r#"{"chars": "W", "length": 4, "format": "png"}"#).unwrap();
+        assert!(captcha.image.starts_with("data:image/png;base64,"));
+        assert_eq!(captcha.token, None);
+
+        let captcha = render(r#"{"chars": "W", "secret": "wasm-secret", "expires": 60}"#).unwrap();
+        let token = captcha.token.unwrap();
+        assert_eq!(crate::verify(&token, "wwwww", "wasm-secret"), Some(true));
+
+        assert!(render("{").is_err());
+        assert!(render(r#"{"length": "five"}"#).is_err());
+    }
+}
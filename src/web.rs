@@ -0,0 +1,257 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Captcha, CaptchaBuilder, CaptchaStore};
+#[cfg(feature = "stateless")]
+use crate::{Key, KeySet, MemoryReplayGuard, TokenContext, Verifier};
+
+/// Generator and verification backend shared by the web framework integrations.
+///
+/// Challenges are identified by a token sent to the client with the image. With a
+/// [`CaptchaStore`] the token is the store id. With a secret or a [`Verifier`] it
+/// is a signed stateless token. Either way a token is accepted only once.
+///
+/// Cloning is cheap, the builder and backend are shared.
+#[derive(Clone)]
+pub struct CaptchaService {
+    builder: Arc<CaptchaBuilder>,
+    backend: Backend,
+    expires: u64,
+}
+
+#[derive(Clone)]
+enum Backend {
+    Store(Arc<dyn CaptchaStore>),
+    #[cfg(feature = "stateless")]
+    Tokens(Arc<Verifier>),
+}
+
+/// A new captcha and the token identifying it.
+pub struct Challenge {
+    pub token: String,
+    pub captcha: Captcha,
+}
+
+/// JSON body describing a [`Challenge`]: `{"token", "image", "expires_in"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChallengeBody {
+    pub token: String,
+    /// The image as a data URI, see [`Captcha::to_base64`].
+    pub image: String,
+    pub expires_in: u64,
+}
+
+/// JSON or form body submitting an answer: `{"token", "answer"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifyBody {
+    pub token: String,
+    pub answer: String,
+}
+
+impl CaptchaService {
+    /// Keep answers in `store`, e.g. a [`crate::MemoryStore`].
+    pub fn with_store(builder: CaptchaBuilder, store: Arc<dyn CaptchaStore>) -> Self {
+        CaptchaService {
+            builder: Arc::new(builder),
+            backend: Backend::Store(store),
+            expires: 300,
+        }
+    }
+
+    /// Issue stateless tokens signed with `secret`, compatible with
+    /// [`crate::verify`], and reject replays with a [`MemoryReplayGuard`].
+    #[cfg(feature = "stateless")]
+    pub fn with_secret(builder: CaptchaBuilder, secret: &str) -> Self {
+        let verifier = Verifier::new(KeySet::new(Key::unnamed(secret)))
+            .replay_guard(Arc::new(MemoryReplayGuard::new()));
+        Self::with_verifier(builder, verifier)
+    }
+
+    /// Issue stateless tokens with the current key of `verifier`, which also
+    /// provides key rotation, rate limiting and replay protection.
+    #[cfg(feature = "stateless")]
+    pub fn with_verifier(builder: CaptchaBuilder, verifier: Verifier) -> Self {
+        CaptchaService {
+            builder: Arc::new(builder),
+            backend: Backend::Tokens(Arc::new(verifier)),
+            expires: 300,
+        }
+    }
+
+    /// Seconds a challenge stays valid, from 1 to 86400.
+    pub fn expires(mut self, seconds: u64) -> Self {
+        self.expires = seconds.clamp(1, 86_400);
+        self
+    }
+
+    pub fn expires_in(&self) -> u64 {
+        self.expires
+    }
+
+    pub fn builder(&self) -> &CaptchaBuilder {
+        &self.builder
+    }
+
+    /// Generate a captcha and register it with the backend.
+    ///
+    /// Returns `None` if no token can be signed, e.g. with a verification-only key.
+    pub fn issue(&self) -> Option<Challenge> {
+        let captcha = self.builder.generate();
+        let token = match &self.backend {
+            Backend::Store(store) => store.insert(&captcha, Duration::from_secs(self.expires)),
+            #[cfg(feature = "stateless")]
+            Backend::Tokens(verifier) => {
+                captcha.as_token_with_keys(verifier.keys(), self.expires)?
+            }
+        };
+        Some(Challenge { token, captcha })
+    }
+
+    /// Check an answer. `client` (e.g. an IP address) is passed to the rate limiter
+    /// of a [`Verifier`] backend.
+    pub fn verify(&self, token: &str, answer: &str, client: Option<&str>) -> bool {
+        match &self.backend {
+            Backend::Store(store) => {
+                let _ = client;
+                store.verify(token, answer)
+            }
+            #[cfg(feature = "stateless")]
+            Backend::Tokens(verifier) => verifier
+                .verify(token, answer, &TokenContext::default(), client)
+                .is_ok(),
+        }
+    }
+}
+
+impl Challenge {
+    pub fn body(&self, expires_in: u64) -> ChallengeBody {
+        ChallengeBody {
+            token: self.token.clone(),
+            image: self.captcha.to_base64(),
+            expires_in,
+        }
+    }
+}
+
+/// Why a captcha-protected request was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CaptchaRejection {
+    /// The body isn't valid JSON or form data for the expected type (400).
+    InvalidBody,
+    /// `captcha_token` or `captcha_answer` is missing (400).
+    Missing,
+    /// The answer is wrong, or the token is invalid, expired or already used (403).
+    Failed,
+    /// No [`CaptchaService`] was registered with the application (500).
+    NotConfigured,
+    /// The client asked for too many challenges, try again later (429).
+    RateLimited,
+}
+
+impl CaptchaRejection {
+    /// HTTP status code of the response.
+    pub fn status(&self) -> u16 {
+        match self {
+            CaptchaRejection::InvalidBody | CaptchaRejection::Missing => 400,
+            CaptchaRejection::Failed => 403,
+            CaptchaRejection::NotConfigured => 500,
+            CaptchaRejection::RateLimited => 429,
+        }
+    }
+
+    #[cfg(any(feature = "actix", feature = "axum"))]
+    pub(crate) fn body(&self) -> ErrorBody {
+        ErrorBody {
+            error: self.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for CaptchaRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            CaptchaRejection::InvalidBody => "invalid request body",
+            CaptchaRejection::Missing => "missing captcha",
+            CaptchaRejection::Failed => "captcha verification failed",
+            CaptchaRejection::NotConfigured => "captcha service not configured",
+            CaptchaRejection::RateLimited => "too many captcha requests",
+        };
+        f.write_str(message)
+    }
+}
+
+impl std::error::Error for CaptchaRejection {}
+
+/// A form or JSON body with the captcha fields next to the application's own.
+#[cfg(any(feature = "actix", feature = "axum"))]
+#[derive(Deserialize)]
+pub(crate) struct Submission<T> {
+    captcha_token: Option<String>,
+    captcha_answer: Option<String>,
+    #[serde(flatten)]
+    inner: T,
+}
+
+#[cfg(any(feature = "actix", feature = "axum"))]
+impl<T> Submission<T> {
+    /// Split the body into the captcha token, the answer and the rest of the body.
+    pub(crate) fn into_parts(self) -> Result<(String, String, T), CaptchaRejection> {
+        let (Some(token), Some(answer)) = (self.captcha_token, self.captcha_answer) else {
+            return Err(CaptchaRejection::Missing);
+        };
+        Ok((token, answer, self.inner))
+    }
+}
+
+#[cfg(any(feature = "actix", feature = "axum"))]
+#[derive(Serialize)]
+pub(crate) struct VerifyResult {
+    pub valid: bool,
+}
+
+#[cfg(any(feature = "actix", feature = "axum"))]
+#[derive(Serialize)]
+pub(crate) struct ErrorBody {
+    pub error: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CaptchaService;
+    use crate::{CaptchaBuilder, MemoryStore};
+    use std::sync::Arc;
+
+    fn builder() -> CaptchaBuilder {
+        CaptchaBuilder::new().text(String::from("shared"))
+    }
+
+    #[test]
+    fn it_issues_and_verifies_with_a_store() {
+        let service = CaptchaService::with_store(builder(), Arc::new(MemoryStore::new()));
+        let challenge = service.issue().unwrap();
+
+        assert!(!service.verify("unknown", "shared", None));
+        assert!(service.verify(&challenge.token, "SHARED", None));
+        assert!(!service.verify(&challenge.token, "shared", None));
+        assert_eq!(challenge.body(service.expires_in()).token, challenge.token);
+    }
+
+    #[cfg(feature = "stateless")]
+    #[test]
+    fn it_issues_single_use_tokens_with_a_secret() {
+        let service = CaptchaService::with_secret(builder(), "web-secret").expires(0);
+        assert_eq!(service.expires_in(), 1);
+
+        let challenge = service.issue().unwrap();
+        assert_eq!(
+            crate::verify(&challenge.token, "shared", "web-secret"),
+            Some(true)
+        );
+        assert!(service.verify(&challenge.token, "shared", None));
+        assert!(!service.verify(&challenge.token, "shared", None));
+    }
+}
@@ -0,0 +1,64 @@
+/* Exercises the C ABI. Built and run by tests/test_capi.rs, or by hand:
+ *   cargo build --features capi
+ *   cc tests/capi/test_capi.c -Iinclude -Ltarget/debug -lcaptcha_rs -o test_capi
+ */
+#include <stdio.h>
+#include <string.h>
+
+#include "captcha_rs.h"
+
+#define CHECK(condition)                                                       \
+    do {                                                                       \
+        if (!(condition)) {                                                    \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__,  \
+                    #condition);                                               \
+            return 1;                                                          \
+        }                                                                      \
+    } while (0)
+
+int main(void) {
+    CaptchaBuilder *builder = captcha_builder_new();
+    CHECK(builder != NULL);
+    captcha_builder_length(builder, 6);
+    captcha_builder_width(builder, 180);
+    captcha_builder_height(builder, 60);
+    captcha_builder_dark_mode(builder, true);
+    CHECK(captcha_builder_chars(builder, "XYZ"));
+    CHECK(captcha_builder_format(builder, CAPTCHA_FORMAT_PNG));
+    CHECK(captcha_builder_add_distortion(builder, CAPTCHA_DISTORTION_RIPPLE, 30));
+    CHECK(!captcha_builder_add_distortion(builder, 42, 30));
+
+    Captcha *captcha = captcha_build(builder);
+    CHECK(captcha != NULL);
+    const char *text = captcha_text(captcha);
+    CHECK(strlen(text) == 6);
+    CHECK(strspn(text, "XYZ") == 6);
+
+    size_t len = 0;
+    const uint8_t *bytes = captcha_bytes(captcha, &len);
+    CHECK(len > 8 && memcmp(bytes, "\x89PNG", 4) == 0);
+    CHECK(strcmp(captcha_mime_type(captcha), "image/png") == 0);
+
+    char *image = captcha_base64(captcha);
+    CHECK(strncmp(image, "data:image/png;base64,", 22) == 0);
+    captcha_string_free(image);
+
+    char *token = captcha_token(captcha, "c-secret", 60);
+    CHECK(token != NULL);
+    CHECK(captcha_verify(token, text, "c-secret") == 1);
+    CHECK(captcha_verify(token, "wrong", "c-secret") == 0);
+    CHECK(captcha_verify(token, text, "other-secret") == -1);
+    CHECK(captcha_verify(NULL, text, "c-secret") == -1);
+    captcha_string_free(token);
+
+    /* The builder stays usable after building */
+    Captcha *second = captcha_build(builder);
+    CHECK(second != NULL);
+    CHECK(captcha_matches(second, captcha_text(second)));
+    captcha_free(second);
+
+    captcha_free(captcha);
+    captcha_builder_free(builder);
+    puts("ok");
+    return 0;
+}
@@ -0,0 +1,87 @@
+#![cfg(feature = "stateless")]
+// The PEM files under `tests/keys` are throwaway test keys generated with openssl.
+
+use captcha_rs::{CaptchaBuilder, Key, KeyAlgorithm, KeySet, verify_with_keys};
+
+const PEPPER: &str = "answer-pepper";
+
+fn key_pair(algorithm: KeyAlgorithm, private_pem: &[u8], public_pem: &[u8]) -> (KeySet, KeySet) {
+    let signing = Key::key_pair("edge-1", algorithm, private_pem, public_pem, PEPPER)
+        .expect("Failed to load key pair");
+    let verifying =
+        Key::public_key("edge-1", algorithm, public_pem, PEPPER).expect("Failed to load key");
+    assert!(signing.can_sign());
+    assert!(!verifying.can_sign());
+    (KeySet::new(signing), KeySet::new(verifying))
+}
+
+fn round_trip(algorithm: KeyAlgorithm, private_pem: &[u8], public_pem: &[u8]) {
+    let (signing, verifying) = key_pair(algorithm, private_pem, public_pem);
+    let captcha = CaptchaBuilder::new().text(String::from("EdGe5")).build();
+
+    let token = captcha
+        .as_token_with_keys(&signing, 60)
+        .expect("Failed to sign token");
+
+    assert_eq!(verify_with_keys(&token, "edge5", &verifying), Some(true));
+    assert_eq!(verify_with_keys(&token, "wrong", &verifying), Some(false));
+
+    // A public key can't issue tokens
+    assert!(captcha.as_token_with_keys(&verifying, 60).is_none());
+
+    // The answer hash depends on the pepper, not on the public key
+    let other_pepper =
+        KeySet::new(Key::public_key("edge-1", algorithm, public_pem, "other-pepper").unwrap());
+    assert_eq!(
+        verify_with_keys(&token, "edge5", &other_pepper),
+        Some(false)
+    );
+}
+
+#[test]
+fn test_ed25519_tokens() {
+    round_trip(
+        KeyAlgorithm::Ed25519,
+        include_bytes!("keys/ed25519_private.pem"),
+        include_bytes!("keys/ed25519_public.pem"),
+    );
+}
+
+#[test]
+fn test_es256_tokens() {
+    round_trip(
+        KeyAlgorithm::Es256,
+        include_bytes!("keys/es256_private.pem"),
+        include_bytes!("keys/es256_public.pem"),
+    );
+}
+
+#[test]
+fn test_rs256_tokens() {
+    round_trip(
+        KeyAlgorithm::Rs256,
+        include_bytes!("keys/rs256_private.pem"),
+        include_bytes!("keys/rs256_public.pem"),
+    );
+}
+
+#[test]
+fn test_rejects_tokens_signed_by_another_key() {
+    let (_, verifying) = key_pair(
+        KeyAlgorithm::Ed25519,
+        include_bytes!("keys/ed25519_private.pem"),
+        include_bytes!("keys/ed25519_public.pem"),
+    );
+    // Same kid and pepper, but signed with a shared secret instead of the private key
+    let forged = CaptchaBuilder::new()
+        .text(String::from("EdGe5"))
+        .build()
+        .as_token_with_keys(&KeySet::new(Key::hmac("edge-1", PEPPER)), 60)
+        .unwrap();
+    assert_eq!(verify_with_keys(&forged, "edge5", &verifying), None);
+}
+
+#[test]
+fn test_rejects_invalid_pem() {
+    assert!(Key::public_key("bad", KeyAlgorithm::Es256, b"not a key", PEPPER).is_none());
+}
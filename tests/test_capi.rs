@@ -0,0 +1,59 @@
+#![cfg(feature = "capi")]
+
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Compile `tests/capi/test_capi.c` against the shared library and generated header,
+/// then run it.
+#[test]
+fn test_c_program() {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let target = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("capi");
+    let program = target.join("test_capi");
+
+    // Build the shared library explicitly, in a separate target directory where
+    // builds with other features can't overwrite it.
+    let built = Command::new(env!("CARGO"))
+        .current_dir(&root)
+        .args([
+            "rustc",
+            "--lib",
+            "--features",
+            "capi",
+            "--crate-type",
+            "cdylib",
+        ])
+        .arg("--target-dir")
+        .arg(&target)
+        .status()
+        .expect("Failed to run cargo");
+    assert!(built.success());
+    let lib_dir = target.join("debug");
+
+    let compiled = Command::new(std::env::var("CC").unwrap_or_else(|_| String::from("cc")))
+        .arg(root.join("tests/capi/test_capi.c"))
+        .args(["-Wall", "-Wextra", "-Werror"])
+        .arg("-I")
+        .arg(root.join("include"))
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg("-lcaptcha_rs")
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-o")
+        .arg(&program)
+        .status()
+        .expect("Failed to run the C compiler");
+    assert!(compiled.success());
+
+    // `cargo test` puts its own library directories first on the search path
+    let output = Command::new(&program)
+        .env_remove("LD_LIBRARY_PATH")
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "ok");
+}
@@ -0,0 +1,81 @@
+#![cfg(feature = "cli")]
+
+use std::process::Command;
+
+fn captcha_rs(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_captcha-rs"))
+        .args(args)
+        .output()
+        .expect("Failed to run captcha-rs");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_cli_prints_data_uris() {
+    let stdout = captcha_rs(&["--text", "hello", "--format", "png", "--dark"]);
+    let (text, image) = stdout.trim_end().split_once('\t').unwrap();
+    assert_eq!(text, "hello");
+    assert!(image.starts_with("data:image/png;base64,"));
+}
+
+#[test]
+fn test_cli_writes_a_directory_as_json() {
+    let dir = std::env::temp_dir().join(format!("captcha-rs-cli-{}", std::process::id()));
+    let args = [
+        "-n",
+        "3",
+        "--out-dir",
+        dir.to_str().unwrap(),
+        "--json",
+        "--seed",
+        "7",
+        "--length",
+        "6",
+        "--size",
+        "200x70",
+        "--warp",
+        "swirl:30",
+    ];
+    let stdout = captcha_rs(&args);
+
+    let lines: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 3);
+    for line in &lines {
+        assert_eq!(line["text"].as_str().unwrap().len(), 6);
+        let file = line["file"].as_str().unwrap();
+        let image = image::open(file).unwrap();
+        assert_eq!((image.width(), image.height()), (200, 70));
+    }
+
+    // The same seed gives the same captchas
+    assert_eq!(captcha_rs(&args), stdout);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_cli_rejects_bad_flags() {
+    let status = Command::new(env!("CARGO_BIN_EXE_captcha-rs"))
+        .args(["--warp", "twist:10"])
+        .output()
+        .unwrap()
+        .status;
+    assert!(!status.success());
+}
+
+#[cfg(feature = "stateless")]
+#[test]
+fn test_cli_signs_tokens() {
+    let stdout = captcha_rs(&["--json", "--secret", "cli-secret"]);
+    let line: serde_json::Value = serde_json::from_str(stdout.trim_end()).unwrap();
+    let token = line["token"].as_str().unwrap();
+    let text = line["text"].as_str().unwrap();
+    assert_eq!(captcha_rs::verify(token, text, "cli-secret"), Some(true));
+}
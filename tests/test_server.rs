@@ -0,0 +1,171 @@
+#![cfg(feature = "server")]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::process::{Child, Command, Stdio};
+
+/// A running `captcha-server`, killed on drop.
+struct TestServer {
+    child: Child,
+    addr: String,
+}
+
+impl TestServer {
+    fn start(name: &str, config: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "captcha-rs-server-{}-{}.json",
+            name,
+            std::process::id()
+        ));
+        std::fs::write(&path, config).unwrap();
+
+        let mut child = Command::new(env!("CARGO_BIN_EXE_captcha-server"))
+            .arg(&path)
+            .env_remove("CAPTCHA_SECRET")
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("Failed to start captcha-server");
+
+        let mut line = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let addr = line
+            .trim()
+            .strip_prefix("listening on http://")
+            .expect("Unexpected server output")
+            .to_string();
+        TestServer { child, addr }
+    }
+
+    /// Send a request and return the status, headers and body.
+    fn request(&self, method: &str, path: &str, body: &str) -> (u16, String, Vec<u8>) {
+        let mut stream = TcpStream::connect(&self.addr).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            method,
+            path,
+            self.addr,
+            body.len(),
+            body
+        )
+        .unwrap();
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+        let split = response
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .unwrap();
+        let head = String::from_utf8(response[..split].to_vec()).unwrap();
+        let status = head[9..12].parse().unwrap();
+        (status, head, response[split + 4..].to_vec())
+    }
+
+    fn json(&self, method: &str, path: &str, body: &str) -> (u16, serde_json::Value) {
+        let (status, _, body) = self.request(method, path, body);
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn test_server_with_store() {
+    let server = TestServer::start(
+        "store",
+        r#"{"bind": "127.0.0.1:0", "captcha": {"chars": "K", "length": 4}}"#,
+    );
+
+    let (status, captcha) = server.json("GET", "/captcha", "");
+    assert_eq!(status, 200);
+    assert!(
+        captcha["image"]
+            .as_str()
+            .unwrap()
+            .starts_with("data:image/jpeg;base64,")
+    );
+    assert_eq!(captcha["expires_in"], 300);
+    let id = captcha["token"].as_str().unwrap();
+
+    let wrong = format!(r#"{{"token": "{}", "answer": "nope"}}"#, id);
+    assert_eq!(server.json("POST", "/verify", &wrong).1["valid"], false);
+
+    // Ids are consumed by the first attempt
+    let right = format!(r#"{{"token": "{}", "answer": "kkkk"}}"#, id);
+    assert_eq!(server.json("POST", "/verify", &right).1["valid"], false);
+
+    let (status, head, image) = server.request("GET", "/captcha/image", "");
+    assert_eq!(status, 200);
+    assert!(head.contains("Content-Type: image/jpeg"));
+    assert!(head.contains("Cache-Control: no-store"));
+    assert!(image.starts_with(&[0xFF, 0xD8]));
+    let id = head
+        .lines()
+        .find_map(|line| line.strip_prefix("X-Captcha-Token: "))
+        .unwrap();
+
+    let right = format!(r#"{{"token": "{}", "answer": "KKKK"}}"#, id);
+    assert_eq!(server.json("POST", "/verify", &right).1["valid"], true);
+
+    let (status, result) = server.json("POST", "/verify", "not json");
+    assert_eq!(status, 400);
+    assert_eq!(
+        result["error"],
+        captcha_rs::CaptchaRejection::InvalidBody.to_string()
+    );
+    assert_eq!(
+        server.json("POST", "/verify", r#"{"answer": "KKKK"}"#).0,
+        400
+    );
+    assert_eq!(server.json("GET", "/verify", "").0, 405);
+    assert_eq!(server.json("GET", "/missing", "").0, 404);
+}
+
+#[cfg(feature = "stateless")]
+#[test]
+fn test_server_with_tokens() {
+    let server = TestServer::start(
+        "stateless",
+        r#"{"bind": "127.0.0.1:0", "secret": "server-secret", "captcha": {"chars": "Q", "length": 3, "format": "png"}}"#,
+    );
+
+    let (status, captcha) = server.json("GET", "/captcha", "");
+    assert_eq!(status, 200);
+    assert!(
+        captcha["image"]
+            .as_str()
+            .unwrap()
+            .starts_with("data:image/png;base64,")
+    );
+    let token = captcha["token"].as_str().unwrap();
+
+    let right = format!(r#"{{"token": "{}", "answer": "qqq"}}"#, token);
+    assert_eq!(server.json("POST", "/verify", &right).1["valid"], true);
+
+    // Tokens are only accepted once
+    let (status, result) = server.json("POST", "/verify", &right);
+    assert_eq!(status, 200);
+    assert_eq!(result["valid"], false);
+
+    let token = server.json("GET", "/captcha", "").1["token"].clone();
+    let wrong = format!(r#"{{"token": {}, "answer": "abc"}}"#, token);
+    assert_eq!(server.json("POST", "/verify", &wrong).1["valid"], false);
+
+    let (status, head, _) = server.request("GET", "/captcha/image", "");
+    assert_eq!(status, 200);
+    let token = head
+        .lines()
+        .find_map(|line| line.strip_prefix("X-Captcha-Token: "))
+        .unwrap();
+    let right = format!(r#"{{"token": "{}", "answer": "QQQ"}}"#, token);
+    assert_eq!(server.json("POST", "/verify", &right).1["valid"], true);
+}
@@ -0,0 +1,41 @@
+#![cfg(feature = "stateless")]
+// The default clock and random source are process-wide, so they are tested in
+// their own binary.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use captcha_rs::{
+    CaptchaBuilder, DEFAULT_LEEWAY, FixedClock, VerifyError, set_default_clock, set_random_source,
+    verify, verify_detailed,
+};
+
+static SOURCE_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+#[test]
+fn test_default_clock_and_random_source() {
+    assert!(set_random_source(Box::new(|bytes: &mut [u8]| {
+        SOURCE_CALLS.fetch_add(1, Ordering::Relaxed);
+        bytes.fill(7);
+    })));
+    assert!(!set_random_source(Box::new(|_: &mut [u8]| {})));
+
+    let clock = Arc::new(FixedClock::new(1_000_000));
+    assert!(set_default_clock(clock.clone()));
+    assert!(!set_default_clock(Arc::new(FixedClock::new(0))));
+
+    let captcha = CaptchaBuilder::new().text(String::from("clock")).build();
+    let token = captcha.as_token("sources-secret", 60).unwrap();
+    assert!(SOURCE_CALLS.load(Ordering::Relaxed) > 0);
+    assert_eq!(verify(&token, "clock", "sources-secret"), Some(true));
+
+    // The token expired by the default clock, whatever the system time is
+    clock.advance(60 + DEFAULT_LEEWAY + 1);
+    assert_eq!(
+        verify_detailed(&token, "clock", "sources-secret"),
+        Err(VerifyError::Expired)
+    );
+
+    // Drawing is seeded from the source, so images are generated as usual
+    assert!(CaptchaBuilder::new().build().to_base64().len() > 100);
+}